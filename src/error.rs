@@ -6,23 +6,66 @@ pub enum CreationError {
     FileNotFound,
     #[error("failed to open '/dev/net/tun': permission denied")]
     PermissionDenied,
+    #[error("failed to create tun/tap device: CAP_NET_ADMIN capability is required")]
+    MissingCapability,
     #[error("failed to open '/dev/net/tun': {0}")]
     UnableToOpenFile(#[from] std::io::Error),
     #[error("failed to modify tun/tap device: {0}")]
-    IoctlError(#[from] nix::Error),
+    IoctlError(nix::Error),
     #[error("failed to create tun/tap device: {0}")]
     InvalidName(#[from] StringError),
+    #[error("invalid MAC address: multicast or broadcast address cannot be assigned to device")]
+    InvalidMacAddress,
+    #[error("invalid MAC address: address is already used on the same network segment")]
+    MacAddressInUse,
+    #[error("failed to create tun/tap device: interface index {0} is already in use")]
+    IndexInUse(u32),
+    #[error("payload of {len} bytes exceeds MTU of the device ({mtu} bytes), lower MTU (or MSS) of the sender")]
+    PayloadTooLarge { len: usize, mtu: usize },
+    #[error("failed to apply {step} of interface config: {source}")]
+    ConfigFailed { step: &'static str, #[source] source: Box<CreationError> },
+    #[error("failed to modify tun/tap device: device was removed")]
+    DeviceGone,
+    #[error("invalid filter expression: {0}")]
+    InvalidFilter(String),
+    #[error("failed to pass tun/tap device over unix socket: {0}")]
+    FdPassingFailed(std::io::Error),
+}
 
+/// Errors of ioctl and netlink calls. `ENODEV`, which is reported for interface removed in the meantime
+/// (e.g. non-persistent device whose last file descriptor was closed elsewhere), and `EBADFD`, which is
+/// reported by tun file descriptor detached from removed device, are turned into [CreationError::DeviceGone],
+/// so they can be told apart from configuration errors.
+impl From<nix::Error> for CreationError {
+    fn from(e: nix::Error) -> Self {
+        match e {
+            nix::Error::Sys(nix::errno::Errno::ENODEV) | nix::Error::Sys(nix::errno::Errno::EBADFD) => {
+                CreationError::DeviceGone
+            }
+            e => CreationError::IoctlError(e),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum StringError {
-    #[error("c_string too long, can be at most {0} characters")]
-    StringTooLong(usize),
+    #[error("c_string too long, has {len} characters, but can be at most {max} characters")]
+    StringTooLong { max: usize, len: usize },
     #[error("rust string contains null at position {0}")]
     UnexpectedNull(usize),
     #[error("string contains invalid character at position {0}")]
     InvalidCharacter(usize),
     #[error("c_string does not contains null terminator")]
     MangledString,
-}
\ No newline at end of file
+}
+
+/// Error contained in `InvalidData` error returned by read of frame with invalid checksum,
+/// see [DeviceBuilder::validate_checksums](crate::prelude::DeviceBuilder::validate_checksums).
+#[cfg(feature = "etherparse")]
+#[derive(Error, Debug)]
+#[error("frame has invalid IPv4 header, TCP or UDP checksum")]
+pub struct ChecksumError;
+
+/// Error returned when frame cannot be parsed.
+#[cfg(feature = "etherparse")]
+pub type ParseError = etherparse::ReadError;
@@ -0,0 +1,226 @@
+use crate::unix::device::{Device, DeviceMode, MAX_FRAME_SIZE};
+use mio::{unix::EventedFd, Evented, Poll as MioPoll, PollOpt, Ready, Token};
+use tokio::io::{AsyncRead, AsyncWrite, PollEvented};
+use std::{
+    fmt::{Display, Debug, Formatter, Result as FmtResult},
+    future::poll_fn,
+    io::Result as IoResult,
+    net::Shutdown,
+    os::unix::io::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+impl Evented for Device {
+    fn register(&self, poll: &MioPoll, token: Token, interest: Ready, opts: PollOpt) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &MioPoll, token: Token, interest: Ready, opts: PollOpt) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &MioPoll) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).deregister(poll)
+    }
+}
+
+impl Device {
+    /// Register device in tokio reactor, for frameworks built directly on top of readiness events.
+    /// Device is switched to non-blocking mode.
+    ///
+    /// # Remarks
+    ///
+    /// Readiness is polled with `poll_read_ready`/`poll_write_ready`, frames are then read and written
+    /// through `get_mut()`, and readiness must be cleared with `clear_read_ready`/`clear_write_ready`
+    /// after `WouldBlock` error. For plain `AsyncRead`/`AsyncWrite` use [Device::into_async].
+    ///
+    /// # Errors
+    ///
+    /// Must be called from within tokio runtime, otherwise device cannot be registered in reactor
+    /// and error is returned.
+    pub fn into_poll_evented(self) -> IoResult<PollEvented<Device>> {
+        self.set_nonblocking(true)?;
+        PollEvented::new(self)
+    }
+
+    /// Convert device into [AsyncDevice], driven by tokio reactor. Device is switched to non-blocking mode.
+    ///
+    /// # Errors
+    ///
+    /// Must be called from within tokio runtime, otherwise device cannot be registered in reactor
+    /// and error is returned.
+    pub fn into_async(self) -> IoResult<AsyncDevice> {
+        Ok(AsyncDevice {
+            inner: self.into_poll_evented()?,
+        })
+    }
+}
+
+/// Asynchronous network tun or tap device, created with [Device::into_async].
+pub struct AsyncDevice {
+    inner: PollEvented<Device>,
+}
+
+impl AsyncDevice {
+    pub fn name(&self) -> &str {
+        self.inner.get_ref().name()
+    }
+
+    pub fn mode(&self) -> DeviceMode {
+        self.inner.get_ref().mode()
+    }
+
+    /// Get reference to the underlying device.
+    pub fn get_ref(&self) -> &Device {
+        self.inner.get_ref()
+    }
+
+    /// Shut down reading, writing or both directions of the device, see [Device::shutdown].
+    /// Subsequent reads or writes in the shut down direction fail with `NotConnected` error.
+    /// [AsyncWrite::poll_shutdown] shuts down writing.
+    ///
+    /// # Remarks
+    ///
+    /// Shutdown requires exclusive access to the device, so it cannot be called while read or write of the device
+    /// is pending, and no pending task is woken. To stop a task waiting for frames, drop or cancel its future.
+    pub fn shutdown(&mut self, how: Shutdown) {
+        self.inner.get_mut().shutdown(how);
+    }
+
+    /// Wait for frame and read it, together with frames, which are already available, at most `max` frames,
+    /// e.g. to read busy device in batches, while other tasks and devices are served between them.
+    ///
+    /// # Returns
+    ///
+    /// Number of frames read and appended to `out`, each as separate vector. Zero is returned only if `max` is 0.
+    ///
+    /// # Remarks
+    ///
+    /// Only the first frame is waited for, reading stops when the device would block or `max` frames were read.
+    /// If reading of frame other than the first one fails, reading stops and number of frames read before
+    /// it is returned, error is dropped (persistent error, e.g. of removed device, is returned by the next read).
+    pub async fn read_batch(&mut self, max: usize, out: &mut Vec<Vec<u8>>) -> IoResult<usize> {
+        if max == 0 {
+            return Ok(0);
+        }
+        let mut buf = vec![0u8; MAX_FRAME_SIZE + self.get_ref().prefix_len()];
+        let read = poll_fn(|cx| Pin::new(&mut *self).poll_read(cx, &mut buf)).await?;
+        out.push(buf[..read].to_vec());
+        let mut count = 1;
+        while count < max {
+            let poll = poll_fn(|cx| Poll::Ready(Pin::new(&mut *self).poll_read(cx, &mut buf))).await;
+            match poll {
+                Poll::Ready(Ok(read)) => out.push(buf[..read].to_vec()),
+                Poll::Ready(Err(_)) | Poll::Pending => break,
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl Display for AsyncDevice {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Debug for AsyncDevice {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Async{:?}", self.inner.get_ref())
+    }
+}
+
+impl AsyncRead for AsyncDevice {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IoResult<usize>> {
+        self.inner.get_ref().check_shutdown(Shutdown::Read)?;
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AsyncDevice {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        self.inner.get_ref().check_shutdown(Shutdown::Write)?;
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        self.shutdown(Shutdown::Write);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unix::DeviceBuilder;
+    use std::{io::ErrorKind, net::{Ipv4Addr, UdpSocket}};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Open tun device with address `10.92.<subnet>.1/24` and socket sending to a peer behind it.
+    fn tun_with_peer(subnet: u8) -> (Device, UdpSocket) {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, subnet, 1), 24, true).unwrap();
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.connect((Ipv4Addr::new(10, 92, subnet, 2), 9)).unwrap();
+        (device, socket)
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    async fn read_after_into_async() {
+        let (device, socket) = tun_with_peer(1);
+        let mut device = device.into_async().unwrap();
+        socket.send(b"ping").unwrap();
+        let mut buf = [0u8; 1500];
+        // Kernel may send its own packets (e.g. IPv6 router solicitation) once the device is up
+        for _ in 0..16 {
+            let read = device.read(&mut buf).await.unwrap();
+            if buf[..read].ends_with(b"ping") {
+                return;
+            }
+        }
+        panic!("sent packet was not read");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    async fn read_after_write_shutdown() {
+        let (device, socket) = tun_with_peer(2);
+        let mut device = device.into_async().unwrap();
+        AsyncWriteExt::shutdown(&mut device).await.unwrap();
+        let error = device.write(&[0x45; 20]).await.unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotConnected);
+        socket.send(b"still readable").unwrap();
+        let mut buf = [0u8; 1500];
+        for _ in 0..16 {
+            let read = device.read(&mut buf).await.unwrap();
+            if buf[..read].ends_with(b"still readable") {
+                return;
+            }
+        }
+        panic!("sent packet was not read after write shutdown");
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    async fn read_batch_budget() {
+        let (device, socket) = tun_with_peer(3);
+        let mut device = device.into_async().unwrap();
+        for i in 0..10u8 {
+            socket.send(&[i]).unwrap();
+        }
+        let mut frames = Vec::new();
+        assert_eq!(device.read_batch(4, &mut frames).await.unwrap(), 4);
+        assert_eq!(frames.len(), 4);
+        // The rest is read until the device would block, not until the budget is exhausted
+        let read = device.read_batch(100, &mut frames).await.unwrap();
+        assert!((6..100).contains(&read));
+        assert_eq!(frames.len(), 4 + read);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::unix::{device::MAX_FRAME_SIZE, r#async::device::AsyncDevice};
+use tokio::io::AsyncRead;
+use std::{
+    future::poll_fn,
+    io::Result as IoResult,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Set of asynchronous devices, which are read together by single task.
+///
+/// # Remarks
+///
+/// Devices are identified by index, which is assigned when device is added and does not change
+/// when other devices are removed.
+#[derive(Debug)]
+pub struct AsyncDeviceSet {
+    devices: Vec<Option<AsyncDevice>>,
+    /// Index of the device, which is polled first, rotated to read devices fairly.
+    next: usize,
+    buf: Vec<u8>,
+}
+
+impl AsyncDeviceSet {
+    /// Create empty set.
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            next: 0,
+            buf: vec![0u8; MAX_FRAME_SIZE],
+        }
+    }
+
+    /// Add device to the set, returns its index.
+    pub fn add(&mut self, device: AsyncDevice) -> usize {
+        // Shared buffer must fit the largest frame of any device, including its packet info and virtio header
+        let size = MAX_FRAME_SIZE + device.get_ref().prefix_len();
+        if self.buf.len() < size {
+            self.buf.resize(size, 0);
+        }
+        match self.devices.iter().position(Option::is_none) {
+            Some(index) => {
+                self.devices[index] = Some(device);
+                index
+            }
+            None => {
+                self.devices.push(Some(device));
+                self.devices.len() - 1
+            }
+        }
+    }
+
+    /// Remove device with given index from the set, returns `None` if there is no such device.
+    pub fn remove(&mut self, index: usize) -> Option<AsyncDevice> {
+        self.devices.get_mut(index)?.take()
+    }
+
+    /// Get device with given index.
+    pub fn get(&self, index: usize) -> Option<&AsyncDevice> {
+        self.devices.get(index)?.as_ref()
+    }
+
+    /// Returns number of devices in the set.
+    pub fn len(&self) -> usize {
+        self.devices.iter().filter(|device| device.is_some()).count()
+    }
+
+    /// Returns true, if there are no devices in the set.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wait for frame from any device in the set.
+    ///
+    /// # Returns
+    ///
+    /// Index of the device and the frame read from it, or error which occurred while reading it.
+    ///
+    /// # Remarks
+    ///
+    /// Devices are polled in rotating order, so busy device cannot starve the others.
+    /// If set is empty, returned future never completes.
+    pub async fn next_frame(&mut self) -> (usize, IoResult<Vec<u8>>) {
+        poll_fn(|cx| self.poll_next_frame(cx)).await
+    }
+
+    fn poll_next_frame(&mut self, cx: &mut Context<'_>) -> Poll<(usize, IoResult<Vec<u8>>)> {
+        let count = self.devices.len();
+        for offset in 0..count {
+            let index = (self.next + offset) % count;
+            let device = match self.devices[index].as_mut() {
+                Some(device) => device,
+                None => continue,
+            };
+            if let Poll::Ready(result) = Pin::new(device).poll_read(cx, &mut self.buf) {
+                self.next = (index + 1) % count;
+                return Poll::Ready((index, result.map(|read| self.buf[..read].to_vec())));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Default for AsyncDeviceSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unix::{DeviceBuilder, DeviceMode};
+    use std::{
+        net::{Ipv4Addr, UdpSocket},
+        time::Duration,
+    };
+
+    /// Wait for the next frame of the set, failing after 5 seconds.
+    async fn next_frame(set: &mut AsyncDeviceSet) -> (usize, Vec<u8>) {
+        let (index, frame) = tokio::time::timeout(Duration::from_secs(5), set.next_frame()).await.unwrap();
+        (index, frame.unwrap())
+    }
+
+    #[tokio::test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    async fn read_three_devices() {
+        let mut set = AsyncDeviceSet::new();
+        let mut sockets = Vec::new();
+        for subnet in 21..24 {
+            let mut device = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+            device.setup_ipv4(Ipv4Addr::new(10, 92, subnet, 1), 24, true).unwrap();
+            let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+            socket.connect((Ipv4Addr::new(10, 92, subnet, 2), 9)).unwrap();
+            assert_eq!(set.add(device.into_async().unwrap()), sockets.len());
+            sockets.push(socket);
+        }
+        // Only one device has traffic, kernel may send its own packets (e.g. IPv6 router solicitation)
+        sockets[1].send(b"only").unwrap();
+        loop {
+            let (index, frame) = next_frame(&mut set).await;
+            if frame.ends_with(b"only") {
+                assert_eq!(index, 1);
+                break;
+            }
+        }
+        for (index, socket) in sockets.iter().enumerate() {
+            socket.send(format!("device {}", index).as_bytes()).unwrap();
+        }
+        let mut pending = vec![true; sockets.len()];
+        while pending.contains(&true) {
+            let (index, frame) = next_frame(&mut set).await;
+            if frame.ends_with(format!("device {}", index).as_bytes()) {
+                pending[index] = false;
+            }
+        }
+        assert!(set.remove(1).is_some());
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{
+    unix::{
+        link::{LinkEvent, LinkEvents, LinkState},
+        netlink::NetlinkSocket,
+    },
+};
+use mio::{unix::EventedFd, Evented, Poll as MioPoll, PollOpt, Ready, Token};
+use tokio::{io::PollEvented, stream::Stream};
+use std::{
+    io::{Error as IoError, Result as IoResult},
+    os::unix::io::AsRawFd,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+impl Evented for NetlinkSocket {
+    fn register(&self, poll: &MioPoll, token: Token, interest: Ready, opts: PollOpt) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &MioPoll, token: Token, interest: Ready, opts: PollOpt) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &MioPoll) -> IoResult<()> {
+        EventedFd(&self.as_raw_fd()).deregister(poll)
+    }
+}
+
+impl LinkEvents {
+    /// Convert iterator into asynchronous stream of device changes, driven by tokio reactor.
+    /// Same as the iterator, stream ends after [LinkEvent::Removed]. Errors of receiving notifications
+    /// are wrapped into `Other` error.
+    ///
+    /// # Errors
+    ///
+    /// Must be called from within tokio runtime, otherwise socket cannot be registered in reactor
+    /// and error is returned.
+    pub fn into_stream(self) -> IoResult<LinkEventStream> {
+        Ok(LinkEventStream {
+            socket: PollEvented::new(self.socket)?,
+            state: self.state,
+        })
+    }
+}
+
+/// Asynchronous stream of device changes, created with [LinkEvents::into_stream].
+#[derive(Debug)]
+pub struct LinkEventStream {
+    socket: PollEvented<NetlinkSocket>,
+    state: LinkState,
+}
+
+impl Stream for LinkEventStream {
+    type Item = IoResult<LinkEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.state.next_event() {
+                return Poll::Ready(event.map(Ok));
+            }
+            match self.socket.poll_read_ready(cx, Ready::readable()) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(_)) => (),
+            }
+            // Notifications are received until the socket would block, so readiness can be cleared
+            let this = &mut *self;
+            let result = this.state.receive(this.socket.get_mut());
+            if let Err(e) = this.socket.clear_read_ready(cx, Ready::readable()) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if let Err(e) = result {
+                return Poll::Ready(Some(Err(IoError::other(e))));
+            }
+        }
+    }
+}
@@ -1 +1,12 @@
-// pub mod async_utils;
\ No newline at end of file
+// pub mod async_utils;
+mod device;
+mod device_set;
+mod stats;
+#[cfg(feature = "netlink")]
+mod link;
+
+pub use device::*;
+pub use device_set::*;
+pub use stats::*;
+#[cfg(feature = "netlink")]
+pub use link::*;
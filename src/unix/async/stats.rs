@@ -0,0 +1,55 @@
+use crate::unix::stats::{DropDelta, DropMonitor};
+use tokio::{stream::Stream, time::{interval, Interval}};
+use std::{
+    io::Result as IoResult,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+impl DropMonitor {
+    /// Convert monitor into asynchronous stream of drop counter increments, sampled by tokio timer.
+    /// Same as the iterator, stream ends after counters cannot be read.
+    ///
+    /// # Remarks
+    ///
+    /// Must be polled from within tokio runtime with timer enabled.
+    pub fn into_stream(self) -> DropStream {
+        DropStream {
+            interval: interval(self.interval()),
+            monitor: self,
+            started: false,
+            failed: false,
+        }
+    }
+}
+
+/// Asynchronous stream of drop counter increments, created with [DropMonitor::into_stream].
+#[derive(Debug)]
+pub struct DropStream {
+    monitor: DropMonitor,
+    interval: Interval,
+    started: bool,
+    failed: bool,
+}
+
+impl Stream for DropStream {
+    type Item = IoResult<DropDelta>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.failed {
+                return Poll::Ready(None);
+            }
+            if self.interval.poll_tick(cx).is_pending() {
+                return Poll::Pending;
+            }
+            let result = self.monitor.sample();
+            self.failed = result.is_err();
+            // First tick completes immediately, it only takes the initial sample
+            if self.started || result.is_err() {
+                return Poll::Ready(Some(result));
+            }
+            self.started = true;
+        }
+    }
+}
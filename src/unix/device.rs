@@ -1,16 +1,273 @@
 use crate::{
-    error::CreationError,
+    error::{CreationError, StringError},
+    unix::frame::{segment_tcp, finish_checksum, PacketInfo, VirtioNetHeader, VlanTag},
+    unix::pcap::PcapReader,
+    unix::stats::{queue_stats, BenchResult, DropMonitor, QueueCounters, QueueStats},
     unix::utils::{
-        get_fd, InterfaceRequest, tun_set_interface,
+        bridge_learned, ethtool_stats, get_address, get_broadcast_address, get_cloexec, get_coalesce,
+        get_control_socket, get_fd, get_flags, get_hw_address, get_membership_socket, get_mtu, get_netmask,
+        get_tx_queue_len, has_net_admin, interface_index, interface_name, multicast_groups, random_bytes,
+        read_sysfs, recv_fd, send_fd, set_address, set_broadcast_address, set_cloexec, set_coalesce, set_flags,
+        set_hw_address, set_membership, set_mtu, set_multicast, set_netmask, set_nonblocking, set_rxfh_indir,
+        set_tx_queue_len, tun_attach_filter, tun_detach_filter, tun_get_features, tun_get_flags,
+        tun_get_interface, tun_get_vnet_hdr_size, tun_set_filter_ebpf, tun_set_index, tun_set_interface,
+        tun_set_offload, tun_set_persist, tun_set_sndbuf, tun_set_tx_filter,
+        InterfaceFieldReplaceUnit, InterfaceRequest, TxFilter,
     },
 };
+#[cfg(feature = "netlink")]
+use crate::unix::{link::LinkEvents, netlink};
+#[cfg(feature = "pcap-filter")]
+use crate::unix::filter::compile_pcap_filter;
+#[cfg(feature = "etherparse")]
+use crate::{
+    error::ChecksumError,
+    unix::frame::{ip_addresses, verify_checksums, ChecksumStatus},
+};
+#[cfg(feature = "etherparse")]
+use std::net::IpAddr;
+#[cfg(feature = "netlink")]
+use std::{convert::TryInto, net::Ipv6Addr};
 use std::{
     fs::File,
+    path::Path,
     fmt::{Display, Debug, Formatter, Result as FmtResult},
-    io::{Read, Write, Result as IoResult},
+    net::{Ipv4Addr, Shutdown},
+    io::{BufReader, Read, Write, Error as IoError, ErrorKind, IoSlice, IoSliceMut, Result as IoResult},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+        net::UnixStream,
+    },
+    sync::{
+        mpsc::{channel, sync_channel, Receiver, Sender},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
 };
+use nix::errno::Errno;
+use bitflags::bitflags;
+
+/// Largest frame, which can be read from tun/tap device.
+pub(crate) const MAX_FRAME_SIZE: usize = 65536;
+
+/// Length of 802.1Q VLAN tag.
+const VLAN_TAG_LEN: usize = 4;
+
+/// Minimal size of ethernet frame, without frame check sequence.
+const MIN_ETHERNET_FRAME_SIZE: usize = 60;
+
+/// Bounds of send buffer size set by [Device::tune_sndbuf].
+const MIN_SNDBUF: u64 = 64 * 1024;
+const MAX_SNDBUF: u64 = libc::c_int::MAX as u64;
+
+/// First and maximal interval between checks of the device state in [Device::wait_running].
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Maximal number of queues of single multi-queue device (`MAX_TAP_QUEUES` in kernel).
+const MAX_QUEUES: usize = 256;
+
+/// Time to wait for duplicate address detection of link-local address, see [DeviceBuilder::enable_ipv6_ll].
+#[cfg(feature = "netlink")]
+const DAD_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval of checking whether duplicate address detection finished.
+#[cfg(feature = "netlink")]
+const DAD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Number of read frames buffered by channel of [Device::into_channels].
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Priority of ingress filter installed by [Device::set_fwmark].
+#[cfg(feature = "netlink")]
+const FWMARK_FILTER_PRIORITY: u32 = 0xfff0;
+
+/// Returns maximal number of queues, which can be attached to single multi-queue tun/tap device.
+///
+/// # Remarks
+///
+/// Each queue is represented by its own file descriptor, so practical limit also depends on
+/// the number of file descriptors available to the process (`RLIMIT_NOFILE`, often 1024 by default).
+/// If the process runs out of them, opening of the next queue fails with
+/// [CreationError::UnableToOpenFile](crate::error::CreationError), while queues beyond this limit
+/// are refused by the kernel with [CreationError::IoctlError](crate::error::CreationError).
+pub fn max_queues() -> usize {
+    MAX_QUEUES
+}
+
+/// Returns send buffer size for the bandwidth-delay product of the link, see [Device::tune_sndbuf].
+fn sndbuf_size(bandwidth_bps: u64, rtt: Duration) -> libc::c_int {
+    let bdp = (bandwidth_bps as u128 * rtt.as_micros() / 8_000_000).min(MAX_SNDBUF as u128) as u64;
+    bdp.max(MIN_SNDBUF) as libc::c_int
+}
+
+/// Returns IPv4 broadcast address of the network given by address and netmask.
+fn broadcast_address(address: Ipv4Addr, netmask: Ipv4Addr) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from(address) | !u32::from(netmask))
+}
+
+/// Returns prefix length of IPv4 netmask, or `None` if netmask is not contiguous.
+fn netmask_prefix(netmask: Ipv4Addr) -> Option<u32> {
+    let netmask = u32::from(netmask);
+    let prefix = netmask.leading_ones();
+    if netmask.count_ones() == prefix { Some(prefix) } else { None }
+}
+
+/// Flags of tun/tap device (`IFF_*` of `TUNSETIFF` and `TUNGETIFF`), which are understood by this crate.
+const KNOWN_FLAGS: &[(&str, libc::c_short)] = &[
+    ("TUN", libc::IFF_TUN as libc::c_short),
+    ("TAP", libc::IFF_TAP as libc::c_short),
+    ("NAPI", libc::IFF_NAPI as libc::c_short),
+    ("NAPI_FRAGS", libc::IFF_NAPI_FRAGS as libc::c_short),
+    ("MULTI_QUEUE", libc::IFF_MULTI_QUEUE as libc::c_short),
+    ("ATTACH_QUEUE", libc::IFF_ATTACH_QUEUE as libc::c_short),
+    ("DETACH_QUEUE", libc::IFF_DETACH_QUEUE as libc::c_short),
+    ("PERSIST", libc::IFF_PERSIST as libc::c_short),
+    ("NO_PI", libc::IFF_NO_PI as libc::c_short),
+    ("ONE_QUEUE", libc::IFF_ONE_QUEUE as libc::c_short),
+    ("VNET_HDR", libc::IFF_VNET_HDR as libc::c_short),
+    ("TUN_EXCL", libc::IFF_TUN_EXCL as libc::c_short),
+];
+
+/// Returns names (without `IFF_` prefix) and values of flags of tun/tap device (`TUNSETIFF` and `TUNGETIFF`),
+/// which are understood by this crate, see [format_flags].
+///
+/// # Remarks
+///
+/// `IFF_NOFILTER`, reported by `TUNGETIFF` when no socket filter is attached, has the same value as `IFF_NO_PI`,
+/// so it is listed only as `NO_PI`.
+pub fn known_flags() -> &'static [(&'static str, libc::c_short)] {
+    KNOWN_FLAGS
+}
+
+/// Format flags of tun/tap device as names of [known_flags] separated by `|`, e.g. `TAP|NO_PI`. Unknown
+/// flags are appended as single hexadecimal number, empty string is returned for no flags.
+pub fn format_flags(flags: libc::c_short) -> String {
+    let mut names: Vec<String> = KNOWN_FLAGS.iter()
+        .filter(|(_, flag)| flags & flag != 0)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let unknown = KNOWN_FLAGS.iter().fold(flags, |flags, (_, flag)| flags & !flag);
+    if unknown != 0 {
+        names.push(format!("{:#x}", unknown as u16));
+    }
+    names.join("|")
+}
+
+/// Returns true, if network interface with given name exists, including interfaces other than tun/tap.
+///
+/// # Remarks
+///
+/// Result is only a hint, interface can be created or removed right after the check.
+///
+/// # Errors
+///
+/// If name contains nul, [CreationError::InvalidName](crate::error::CreationError) is returned.
+pub fn name_exists(name: &str) -> Result<bool, CreationError> {
+    match interface_index(name) {
+        Ok(_) => Ok(true),
+        Err(CreationError::DeviceGone) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Send file descriptor of the device over unix socket, see [Device::send_over].
+///
+/// # Errors
+///
+/// If descriptor cannot be sent, [CreationError::FdPassingFailed](crate::error::CreationError) is returned.
+pub fn send_fd_to_socket(device: &Device, sock: &UnixStream) -> Result<(), CreationError> {
+    device.send_over(sock).map_err(CreationError::FdPassingFailed)
+}
+
+/// Receive file descriptor of tun/tap device sent by [send_fd_to_socket] (or [Device::send_over]).
+/// Unlike [Device::recv_over], name and mode of the device are read from the descriptor (`TUNGETIFF`).
+///
+/// # Errors
+///
+/// If descriptor cannot be received, [CreationError::FdPassingFailed](crate::error::CreationError) is returned.
+/// If it is not attached to tun/tap device, [CreationError::IoctlError](crate::error::CreationError)
+/// (or [CreationError::DeviceGone](crate::error::CreationError) for removed device) is returned.
+pub fn recv_fd_from_socket(sock: &UnixStream) -> Result<Device, CreationError> {
+    let file = recv_fd(sock).map_err(CreationError::FdPassingFailed)?;
+    let request = tun_get_interface(&file)?;
+    let flags = tun_get_flags(&file)?;
+    let mode = if flags & libc::IFF_TUN != 0 { DeviceMode::Tun } else { DeviceMode::Tap };
+    Device::from_file(file, mode, request.get_name().to_string()?, flags)
+}
+
+/// Returns size of virtio header of the device, or 0 if `vnet_hdr` is not enabled.
+fn vnet_hdr_len(file: &File, vnet_hdr: bool) -> Result<usize, CreationError> {
+    Ok(if vnet_hdr { tun_get_vnet_hdr_size(file)? as usize } else { 0 })
+}
+
+/// Wait until some of `devices` are ready for requested events, at most `timeout`, using single `poll` call.
+///
+/// # Returns
+///
+/// Devices, which are ready, identified by their position in `devices`, with events which occurred. Empty vector
+/// is returned, if timeout elapsed (or wait was interrupted by signal).
+///
+/// # Remarks
+///
+/// Frame buffered by [Device::peek] is reported as readable without waiting. [PollEvents::ERROR] and
+/// [PollEvents::HANGUP] are reported even if they were not requested. Device, which is down, is never
+/// reported writable, as writes to it fail.
+pub fn poll_devices(devices: &[(&Device, PollEvents)], timeout: Duration) -> IoResult<Vec<PollResult>> {
+    let mut fds: Vec<_> = devices.iter().map(|(device, events)| device.as_pollfd(*events)).collect();
+    let stashed = |(device, events): &(&Device, PollEvents)| {
+        device.stash.is_some() && events.contains(PollEvents::READABLE)
+    };
+    // Frame buffered by peek is available without waiting
+    let timeout = timeout.as_millis().min(i32::MAX as u128) as libc::c_int;
+    let timeout = if devices.iter().any(stashed) { 0 } else { timeout };
+    if unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } == -1 {
+        let e = IoError::last_os_error();
+        return if e.kind() == ErrorKind::Interrupted { Ok(Vec::new()) } else { Err(e) };
+    }
+    let ready = devices.iter().zip(fds).enumerate().filter_map(|(index, (device, fd))| {
+        let mut events = PollEvents::from_bits_truncate(fd.revents);
+        if stashed(device) {
+            events |= PollEvents::READABLE;
+        }
+        if events.is_empty() { None } else { Some(PollResult { index, events }) }
+    });
+    Ok(ready.collect())
+}
+
+/// Returns IPv6 link-local addresses of interface with given index, with their flags (`IFA_F_*`).
+#[cfg(feature = "netlink")]
+fn link_local_addresses(
+    socket: &mut netlink::NetlinkSocket,
+    index: u32,
+) -> Result<Vec<(Ipv6Addr, u32)>, CreationError> {
+    use netlink::{AddressMessage, NetlinkMessage};
+
+    let mut addresses = Vec::new();
+    let request = NetlinkMessage::new(libc::RTM_GETADDR, 0, &AddressMessage::new(libc::AF_INET6, index));
+    socket.dump(request, |kind, payload| {
+        let header = match netlink::header::<AddressMessage>(payload) {
+            Some(header) if kind == libc::RTM_NEWADDR && header.index == index => header,
+            _ => return,
+        };
+        let mut address = None;
+        let mut flags = header.flags as u32;
+        for (kind, data) in netlink::attributes::<AddressMessage>(payload) {
+            match kind {
+                netlink::IFA_ADDRESS => address = data.try_into().ok().map(|address: [u8; 16]| Ipv6Addr::from(address)),
+                netlink::IFA_FLAGS => flags = data.try_into().map_or(flags, u32::from_ne_bytes),
+                _ => (),
+            }
+        }
+        if let Some(address) = address.filter(|address| address.segments()[0] & 0xffc0 == 0xfe80) {
+            addresses.push((address, flags));
+        }
+    })?;
+    Ok(addresses)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Mode which device is running in
 /// * `Tun` - Tunnel is layer 3 virtual interface, cannot be bridged. Works with IP Packets
 /// * `Tap` - Terminal Access Point layer 2 virtual interface. Works with Ethernet Frames
@@ -19,18 +276,136 @@ pub enum DeviceMode {
     Tap,
 }
 
+impl DeviceMode {
+    /// Returns MTU, which kernel assigns to newly created device (1500 bytes for both modes).
+    pub fn default_mtu(&self) -> u32 {
+        libc::ETH_DATA_LEN as u32
+    }
+
+    /// Returns size of the largest frame of device with default MTU, without packet info, which is
+    /// sufficient buffer size until actual MTU of the device is known.
+    ///
+    /// # Remarks
+    ///
+    /// Frames of tap device contain ethernet header and may be VLAN tagged.
+    pub fn max_frame_size(&self) -> usize {
+        match self {
+            DeviceMode::Tun => self.default_mtu() as usize,
+            DeviceMode::Tap => self.default_mtu() as usize + libc::ETH_HLEN as usize + VLAN_TAG_LEN,
+        }
+    }
+
+    /// Returns true, if running kernel supports devices in this mode with multiple queues (`IFF_MULTI_QUEUE`).
+    ///
+    /// # Remarks
+    ///
+    /// Features of tun/tap driver are queried (`TUNGETFEATURES`) by the first successful call and cached for
+    /// the whole process. Multiple queues are supported for both tun and tap devices by kernels since 3.8, so
+    /// result is the same for both modes. If `/dev/net/tun` cannot be opened, `false` is returned and features
+    /// are queried again by the next call.
+    pub fn supports_multi_queue(&self) -> bool {
+        static FEATURES: OnceLock<libc::c_uint> = OnceLock::new();
+        let features = cached_features(&FEATURES, || get_fd().and_then(|file| tun_get_features(&file)));
+        let mode = match self {
+            DeviceMode::Tun => libc::IFF_TUN,
+            DeviceMode::Tap => libc::IFF_TAP,
+        };
+        features & (mode | libc::IFF_MULTI_QUEUE) as libc::c_uint == (mode | libc::IFF_MULTI_QUEUE) as libc::c_uint
+    }
+}
+
+/// Returns features stored in `cache`, or queries them and stores them, if query succeeds. Failed query is not
+/// cached (e.g. `/dev/net/tun` could not be opened yet) and no features (0) are returned.
+fn cached_features<E>(cache: &OnceLock<libc::c_uint>, query: impl FnOnce() -> Result<libc::c_uint, E>) -> libc::c_uint {
+    match cache.get() {
+        Some(features) => *features,
+        None => query().map(|features| *cache.get_or_init(|| features)).unwrap_or(0),
+    }
+}
+
 impl Display for DeviceMode {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(f, "{}", if *self == DeviceMode::Tun { "tun" } else { "tap" })
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What happens with device file descriptor in child process, see [Device::prepare_for_fork]
+/// * `CloseOnExec` - Child shares the descriptor after fork, until it calls exec
+/// * `Inherited` - Child shares the descriptor after fork and keeps it also after exec
+pub enum ForkDisposition {
+    CloseOnExec,
+    Inherited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Policy, how MAC address is assigned to newly opened tap device
+/// * `Kernel` - Keep address generated by kernel (random one for every new device)
+/// * `Fixed` - Assign given unicast address
+/// * `Random` - Assign random locally administered unicast address
+pub enum MacPolicy {
+    #[default]
+    Kernel,
+    Fixed([u8; 6]),
+    Random,
+}
+
+#[cfg(feature = "etherparse")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Handling of frames with invalid checksums, see [DeviceBuilder::validate_checksums]
+/// * `Drop` - Frame is dropped and the next frame is read instead
+/// * `Error` - Frame is consumed and `InvalidData` error containing [ChecksumError](crate::error::ChecksumError)
+///   is returned
+pub enum ChecksumPolicy {
+    #[default]
+    Drop,
+    Error,
+}
+
+impl MacPolicy {
+    /// Resolve address which should be assigned to the device, or `None` if kernel address is kept.
+    ///
+    /// # Errors
+    ///
+    /// If fixed address is multicast (or broadcast), [CreationError::InvalidMacAddress](crate::error::CreationError) is returned.
+    fn address(&self) -> Result<Option<[u8; 6]>, CreationError> {
+        match *self {
+            MacPolicy::Kernel => Ok(None),
+            MacPolicy::Fixed(mac) if mac[0] & 0x01 != 0 => Err(CreationError::InvalidMacAddress),
+            MacPolicy::Fixed(mac) => Ok(Some(mac)),
+            MacPolicy::Random => {
+                let mut mac = [0u8; 6];
+                random_bytes(&mut mac)?;
+                // clear multicast bit, set locally administered bit
+                mac[0] = (mac[0] & 0xfe) | 0x02;
+                Ok(Some(mac))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 /// Builder pattern to create new tun or tap device
 pub struct DeviceBuilder<'a> {
     name: Option<&'a str>,
+    name_pattern: bool,
     mode: DeviceMode,
     packet_info: bool,
+    vnet_hdr: bool,
+    require_capability: bool,
+    mac: MacPolicy,
+    ifindex: Option<u32>,
+    mtu: Option<u32>,
+    tx_queue_len: Option<u32>,
+    #[cfg(feature = "netlink")]
+    bridge: Option<&'a str>,
+    bring_up: bool,
+    #[cfg(feature = "netlink")]
+    ipv6_ll: bool,
+    #[cfg(feature = "etherparse")]
+    validate_checksums: bool,
+    #[cfg(feature = "etherparse")]
+    checksum_policy: ChecksumPolicy,
 }
 
 impl<'a> DeviceBuilder<'a> {
@@ -42,7 +417,23 @@ impl<'a> DeviceBuilder<'a> {
         Self {
             mode,
             name: None,
+            name_pattern: false,
             packet_info: false,
+            vnet_hdr: false,
+            require_capability: false,
+            mac: MacPolicy::Kernel,
+            ifindex: None,
+            mtu: None,
+            tx_queue_len: None,
+            #[cfg(feature = "netlink")]
+            bridge: None,
+            bring_up: false,
+            #[cfg(feature = "netlink")]
+            ipv6_ll: false,
+            #[cfg(feature = "etherparse")]
+            validate_checksums: false,
+            #[cfg(feature = "etherparse")]
+            checksum_policy: ChecksumPolicy::Drop,
         }
     }
 
@@ -53,7 +444,23 @@ impl<'a> DeviceBuilder<'a> {
     /// If no name is name is specified, a new device with unique name will be created and
     /// assigned to the device.
     pub fn name(&'a mut self, name: &'a str) -> &'a mut Self {
-        self.name = Some(name.as_ref());
+        self.name = Some(name);
+        self.name_pattern = false;
+        self
+    }
+
+    /// Set name pattern for this device, where `%d` is replaced by the lowest index, for which
+    /// the name is not taken (e.g. `vpn-%d` creates `vpn-0`, `vpn-1`, ...).
+    ///
+    /// # Remarks
+    ///
+    /// Index is chosen by the kernel while creating the device, so there is no race with other
+    /// processes creating devices. Name of the created device is returned by [Device::name].
+    /// Pattern must contain `%d` exactly once and no other `%`, and it must be shorter than `IFNAMSIZ`,
+    /// otherwise [CreationError::InvalidName](crate::error::CreationError) is returned when opening.
+    pub fn name_pattern(&'a mut self, pattern: &'a str) -> &'a mut Self {
+        self.name = Some(pattern);
+        self.name_pattern = true;
         self
     }
 
@@ -64,6 +471,139 @@ impl<'a> DeviceBuilder<'a> {
         self
     }
 
+    /// Set if frames should contain virtio network header ([VirtioNetHeader]), which follows packet
+    /// info (if enabled), describing checksum and segmentation offloads, see [Device::set_offload].
+    pub fn vnet_hdr(&'a mut self, vnet_hdr: bool) -> &'a mut Self {
+        self.vnet_hdr = vnet_hdr;
+        self
+    }
+
+    /// Set if `CAP_NET_ADMIN` capability should be checked before opening the device.
+    ///
+    /// # Remarks
+    ///
+    /// Device can be created by any process with `CAP_NET_ADMIN` in effective capability set
+    /// (including ambient capabilities of non-root users). Without the pre-check, missing capability
+    /// is reported by the kernel as `EPERM` from ioctl call, with the pre-check as
+    /// [CreationError::MissingCapability](crate::error::CreationError). If capabilities cannot be determined
+    /// (e.g. `/proc` is not mounted), check is skipped.
+    pub fn require_capability(&'a mut self, require_capability: bool) -> &'a mut Self {
+        self.require_capability = require_capability;
+        self
+    }
+
+    /// Set how MAC address is assigned to the device, see [MacPolicy]. Address is applied right
+    /// after the device is created.
+    ///
+    /// # Remarks
+    ///
+    /// Only tap devices have MAC address, any other policy than [MacPolicy::Kernel] fails for tun device.
+    pub fn mac(&'a mut self, mac: MacPolicy) -> &'a mut Self {
+        self.mac = mac;
+        self
+    }
+
+    /// Request specific interface index of the created device (`TUNSETIFINDEX`), e.g. to restore
+    /// interface with its original index.
+    ///
+    /// # Remarks
+    ///
+    /// Index is used only when new device is created, attaching to existing device keeps its index.
+    /// If index is already used by another interface, [CreationError::IndexInUse](crate::error::CreationError)
+    /// is returned when device is opened.
+    pub fn ifindex(&'a mut self, ifindex: u32) -> &'a mut Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Set maximal transmission unit of the created device, applied right after the device is created.
+    ///
+    /// # Remarks
+    ///
+    /// If MTU cannot be set (e.g. it is out of range), opening fails and the device is closed, so newly
+    /// created (non-persistent) device is removed.
+    pub fn mtu(&'a mut self, mtu: u32) -> &'a mut Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    /// Set length of transmit queue (`txqueuelen`) of the created device, i.e. how many frames are queued
+    /// for read before they are dropped. Applied right after the device is created, together with MTU.
+    ///
+    /// # Remarks
+    ///
+    /// If queue length cannot be set, opening fails and the device is closed, so newly created
+    /// (non-persistent) device is removed.
+    pub fn txqueuelen(&'a mut self, tx_queue_len: u32) -> &'a mut Self {
+        self.tx_queue_len = Some(tx_queue_len);
+        self
+    }
+
+    /// Attach opened device to existing bridge `name`, see [Device::set_bridge].
+    ///
+    /// # Remarks
+    ///
+    /// If attaching fails, opening fails and the device is closed, so newly created (non-persistent)
+    /// device is removed. Only tap devices can be attached to bridge. If there is no interface `name`,
+    /// [CreationError::IoctlError](crate::error::CreationError) with `ENODEV` is returned.
+    #[cfg(feature = "netlink")]
+    pub fn bridge(&'a mut self, name: &'a str) -> &'a mut Self {
+        self.bridge = Some(name);
+        self
+    }
+
+    /// Set if opened device should be brought up (`IFF_UP`), so it is ready to pass traffic when opened.
+    ///
+    /// # Remarks
+    ///
+    /// Device is brought up as the last step of opening, after MAC address (see [DeviceBuilder::mac]) is
+    /// assigned and device is attached to bridge (see [DeviceBuilder::bridge]).
+    /// If bringing up fails, device is detached from the bridge, opening fails and the device is closed,
+    /// so newly created (non-persistent) device is removed.
+    pub fn bring_up(&'a mut self, bring_up: bool) -> &'a mut Self {
+        self.bring_up = bring_up;
+        self
+    }
+
+    /// Set if opened device should have IPv6 link-local address (`fe80::/64`), which passed duplicate address
+    /// detection, see [Device::ensure_ipv6_link_local]. Device is brought up, as if [DeviceBuilder::bring_up] was set.
+    ///
+    /// # Remarks
+    ///
+    /// Opening waits for duplicate address detection at most 5 seconds, which usually takes about a second.
+    /// Detection is skipped, if it is disabled by `accept_dad` or `dad_transmits` sysctl of the device. If
+    /// address is not usable in time, opening fails and the device is closed, so newly created (non-persistent)
+    /// device is removed.
+    #[cfg(feature = "netlink")]
+    pub fn enable_ipv6_ll(&'a mut self, enable_ipv6_ll: bool) -> &'a mut Self {
+        self.ipv6_ll = enable_ipv6_ll;
+        self
+    }
+
+    /// Set if IPv4 header checksums and TCP and UDP checksums of frames should be verified by every
+    /// read, see [verify_checksums]. Frames with invalid checksum are handled according to
+    /// [DeviceBuilder::checksum_policy].
+    ///
+    /// # Remarks
+    ///
+    /// Verification is done in software, frame is read into intermediate buffer and copied after.
+    /// Frames with checksums left unfinished by checksum offload ([Device::set_offload]) and frames,
+    /// which cannot be parsed, are passed as they are. In blocking mode, dropped frame is replaced
+    /// by waiting for the next one, even if the read was preceded by polling (e.g. [Device::read_deadline]).
+    #[cfg(feature = "etherparse")]
+    pub fn validate_checksums(&'a mut self, validate_checksums: bool) -> &'a mut Self {
+        self.validate_checksums = validate_checksums;
+        self
+    }
+
+    /// Set how frames with invalid checksums are handled, if [DeviceBuilder::validate_checksums] is set.
+    /// By default, they are dropped.
+    #[cfg(feature = "etherparse")]
+    pub fn checksum_policy(&'a mut self, checksum_policy: ChecksumPolicy) -> &'a mut Self {
+        self.checksum_policy = checksum_policy;
+        self
+    }
+
     /// Finish opening of a tun device
     ///
     /// # Errors
@@ -71,19 +611,75 @@ impl<'a> DeviceBuilder<'a> {
     /// * 1. If `/dev/net/tun` does not exists [CreationError::FileNotFound](crate::error::CreationError) error is returned.
     /// * 2. If `NET_ADMIN` capabilities are not set, [CreationError::PermissionDenied](crate::error::CreationError) error is returned
     /// * 3. If something else prevents to open the `/dev/net/tun` [CreationError::UnableToOpenFile](crate::error::CreationError), containing the inner error.
+    ///
+    /// If [DeviceBuilder::require_capability] is set and process does not have `CAP_NET_ADMIN` capability,
+    /// [CreationError::MissingCapability](crate::error::CreationError) is returned before opening `/dev/net/tun`.
+    ///
     /// Name of the device must follow a strict rules, if any of those are not met [CreationError::InvalidName](crate::error::CreationError) is returned:
     /// * 1. Interface name *MUST* contains only ASCII characters
     /// * 2. Interface name *MUST NOT* contain `0` value (null terminator)
     /// * 2. Interface name *MUST* be shorter than `IFNAMSIZ` (shorter, because last char is null terminator)
+    ///
+    /// If MAC address set by [DeviceBuilder::mac] is multicast or broadcast address, [CreationError::InvalidMacAddress](crate::error::CreationError) is returned.
+    ///
     /// If ioctl call fail, [CreationError::IoctlError](crate::error::CreationError) with inner ErrNo is returned.
+    ///
+    /// If MTU or queue length cannot be set, device cannot be attached to bridge set by [DeviceBuilder::bridge],
+    /// or brought up, [CreationError::IoctlError](crate::error::CreationError) is returned.
     pub fn open(&self) -> Result<Device, CreationError> {
-        use libc::{IFF_TUN, IFF_TAP, IFF_NO_PI, c_short, c_int};
+        self.open_with_flags(0)
+    }
+
+    /// Open the device if it does not exists, otherwise attach to the existing one.
+    ///
+    /// # Returns
+    ///
+    /// Opened device and `true` if device was newly created, or `false` if existing device was attached.
+    ///
+    /// # Remarks
+    ///
+    /// Device is first opened exclusively (`IFF_TUN_EXCL`), which fails with `EBUSY` if device
+    /// already exists, so there is no race between checking for existence and creating the device.
+    /// Only persistent devices, which are not attached to other process, can be attached.
+    ///
+    /// # Errors
+    ///
+    /// Same as [DeviceBuilder::open].
+    pub fn open_or_attach(&self) -> Result<(Device, bool), CreationError> {
+        match self.open_with_flags(libc::IFF_TUN_EXCL) {
+            Ok(device) => Ok((device, true)),
+            Err(CreationError::IoctlError(nix::Error::Sys(Errno::EBUSY))) => Ok((self.open()?, false)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns requested index, if it belongs to other interface than the one being opened. `EBUSY`
+    /// is ambiguous, as it is also returned if device with requested name exists and `IFF_TUN_EXCL` is set.
+    fn taken_index(&self) -> Option<u32> {
+        let index = self.ifindex?;
+        let name = interface_name(index)?;
+        if self.name == Some(name.as_str()) { None } else { Some(index) }
+    }
+
+    /// Open the device, with `extra_flags` added to the flags derived from builder settings.
+    fn open_with_flags(&self, extra_flags: libc::c_int) -> Result<Device, CreationError> {
+        use libc::{IFF_TUN, IFF_TAP, IFF_NO_PI, IFF_VNET_HDR, c_short, c_int};
+
+        if self.require_capability && has_net_admin() == Some(false) {
+            return Err(CreationError::MissingCapability);
+        }
+        let mac = self.mac.address()?;
+        if let (true, Some(pattern)) = (self.name_pattern, self.name) {
+            if pattern.matches("%d").count() != 1 || pattern.matches('%').count() != 1 {
+                return Err(StringError::InvalidCharacter(pattern.find('%').unwrap_or(pattern.len())).into());
+            }
+        }
 
         // Get file descriptor to /dev/net/tun
         let file = get_fd()?;
 
         // Build correct flags for ifreq
-        let mut ifr_flags: c_int = 0x0;
+        let mut ifr_flags: c_int = extra_flags;
         if self.mode == DeviceMode::Tun {
             ifr_flags |= IFF_TUN;
         } else {
@@ -94,61 +690,3213 @@ impl<'a> DeviceBuilder<'a> {
             ifr_flags |= IFF_NO_PI;
         }
 
-        let mut ifreq = InterfaceRequest::tun_set_request(if let Some(name) = self.name {
-            name
-        } else {
-            ""
-        }, ifr_flags as c_short)?;
+        if self.vnet_hdr {
+            ifr_flags |= IFF_VNET_HDR;
+        }
+
+        let mut ifreq = InterfaceRequest::tun_set_request(self.name.unwrap_or_default(), ifr_flags as c_short)?;
 
-        tun_set_interface(&file, &mut ifreq)?;
+        if let Some(index) = self.ifindex {
+            tun_set_index(&file, index)?;
+        }
 
-        let name = ifreq.get_name().to_string()?;
+        let name = match tun_set_interface(&file, &mut ifreq) {
+            Err(e @ CreationError::IoctlError(nix::Error::Sys(Errno::EBUSY | Errno::EEXIST))) => {
+                return Err(self.taken_index().map_or(e, CreationError::IndexInUse));
+            }
+            result => result?,
+        };
+        let flags = tun_get_interface(&file)?.get_fru().get_flags() as c_int;
+        let vnet_hdr_len = vnet_hdr_len(&file, self.vnet_hdr)?;
 
-        Ok(Device {
+        if let Some(mac) = mac {
+            let request = InterfaceRequest::new(&name, InterfaceFieldReplaceUnit::hw_address(mac))?;
+            set_hw_address(&get_control_socket()?, &request)?;
+        }
+
+        let device = Device {
             file,
             name,
             mode: self.mode,
-        })
+            packet_info: self.packet_info,
+            vnet_hdr: self.vnet_hdr,
+            vnet_hdr_len,
+            flags,
+            membership: None,
+            stash: None,
+            recv_buf: Vec::new(),
+            strict_mtu: false,
+            counters: QueueCounters::default(),
+            shut_read: false,
+            shut_write: false,
+            #[cfg(feature = "etherparse")]
+            checksums: if self.validate_checksums { Some(self.checksum_policy) } else { None },
+            #[cfg(feature = "netlink")]
+            events: None,
+        };
+        self.prepare(&device)?;
+        Ok(device)
+    }
+
+    /// Set MTU and queue length of opened device, attach it to the bridge and bring it up, as requested.
+    /// If bringing up fails, device is detached from the bridge again. Device is closed by the caller on error.
+    fn prepare(&self, device: &Device) -> Result<(), CreationError> {
+        if self.mtu.is_some() || self.tx_queue_len.is_some() {
+            let socket = get_control_socket()?;
+            if let Some(mtu) = self.mtu {
+                set_mtu(&socket, &device.name, mtu as libc::c_int)?;
+            }
+            if let Some(tx_queue_len) = self.tx_queue_len {
+                set_tx_queue_len(&socket, &device.name, tx_queue_len as libc::c_int)?;
+            }
+        }
+        #[cfg(feature = "netlink")]
+        if let Some(bridge) = self.bridge {
+            let index = match interface_index(bridge) {
+                Err(CreationError::DeviceGone) => return Err(CreationError::IoctlError(nix::Error::Sys(Errno::ENODEV))),
+                result => result?,
+            };
+            device.set_bridge(index)?;
+        }
+        #[cfg(feature = "netlink")]
+        let bring_up = self.bring_up || self.ipv6_ll;
+        #[cfg(not(feature = "netlink"))]
+        let bring_up = self.bring_up;
+        let result = if bring_up { device.update_flags(libc::IFF_UP, libc::IFF_UP) } else { Ok(()) };
+        #[cfg(feature = "netlink")]
+        let result = match result {
+            Ok(()) if self.ipv6_ll => device.ensure_ipv6_link_local(DAD_TIMEOUT).map(|_| ()),
+            result => result,
+        };
+        #[cfg(feature = "netlink")]
+        if result.is_err() && self.bridge.is_some() {
+            let _ = device.leave_bridge();
+        }
+        result
     }
 }
 
-/// Network tun or tap device, created with [DeviceBuilder].
-pub struct Device {
-    file: File,
-    mode: DeviceMode,
+/// Complete receive filtering state of the device, see [Device::set_rx_mode].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RxMode {
+    /// Receive all frames (`IFF_PROMISC`).
+    pub promisc: bool,
+    /// Receive all multicast frames (`IFF_ALLMULTI`).
+    pub allmulti: bool,
+    /// Joined link layer multicast groups.
+    pub groups: Vec<[u8; 6]>,
+}
+
+/// Guard of promiscuous mode enabled by [Device::promiscuous_scope], which restores previous state
+/// of `IFF_PROMISC` flag when dropped.
+#[derive(Debug)]
+pub struct PromiscGuard {
     name: String,
+    was_promiscuous: bool,
 }
 
-impl Device {
-    pub fn name(&self) -> &str {
-        &self.name
+impl PromiscGuard {
+    /// Returns true, if device was already promiscuous when the guard was created, so it stays promiscuous.
+    pub fn was_promiscuous(&self) -> bool {
+        self.was_promiscuous
     }
 }
 
-impl Display for Device {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{}", self.name)
+impl Drop for PromiscGuard {
+    fn drop(&mut self) {
+        if self.was_promiscuous {
+            return;
+        }
+        let result = get_control_socket().and_then(|socket| {
+            let flags = get_flags(&socket, &self.name)?;
+            set_flags(&socket, &self.name, flags & !(libc::IFF_PROMISC as libc::c_short))
+        });
+        if let Err(_e) = result {
+            #[cfg(feature = "log")]
+            log::warn!("{}: failed to disable promiscuous mode: {}", self.name, _e);
+        }
     }
 }
 
-impl Debug for Device {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{:?}Device({})", self.mode, self.name)
+/// Reader of frames, which writes copy of each frame read to mirror device, created with [Device::tee].
+///
+/// # Remarks
+///
+/// Each read returns single frame, like [Device::read]. Packet info is converted like by [Device::duplicate_to].
+/// Frame, which cannot be mirrored (e.g. mirror queue is full in non-blocking mode, or mirror is down), is still
+/// returned, failure is only counted (see [TeeReader::mirror_errors]) and logged with `log` feature enabled.
+///
+/// # Errors
+///
+/// If devices are not in the same mode, `InvalidInput` error is returned by every read, before reading.
+#[derive(Debug)]
+pub struct TeeReader<'a> {
+    device: &'a mut Device,
+    mirror: &'a mut Device,
+    mirror_errors: u64,
+}
+
+impl TeeReader<'_> {
+    /// Returns number of frames, which were read, but could not be mirrored.
+    pub fn mirror_errors(&self) -> u64 {
+        self.mirror_errors
     }
 }
 
-impl Write for Device {
-    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
-        self.file.write(buf)
+impl Read for TeeReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        if self.device.mode != self.mirror.mode {
+            return Err(IoError::new(ErrorKind::InvalidInput, "frames cannot be mirrored between tun and tap device"));
+        }
+        let read = self.device.read(buf)?;
+        if let Err(_e) = self.device.mirror_frame(self.mirror, &buf[..read]) {
+            self.mirror_errors += 1;
+            #[cfg(feature = "log")]
+            log::warn!("{}: failed to mirror frame to {}: {}", self.device.name, self.mirror.name, _e);
+        }
+        Ok(read)
     }
+}
 
-    fn flush(&mut self) -> IoResult<()> {
-        self.file.flush()
+/// Pair of tap devices attached to the same bridge, so frames written to one of them can be read
+/// from the other, e.g. in integration tests.
+///
+/// # Remarks
+///
+/// Devices are not persistent, so they are removed when the pair is dropped. Bridge itself is not
+/// created nor removed.
+#[cfg(feature = "netlink")]
+#[derive(Debug)]
+pub struct BridgedPair(pub Device, pub Device);
+
+#[cfg(feature = "netlink")]
+impl BridgedPair {
+    /// Open two tap devices, attached to existing bridge `bridge` and brought up.
+    ///
+    /// # Errors
+    ///
+    /// Same as [DeviceBuilder::open] with [DeviceBuilder::bridge].
+    pub fn open(bridge: &str) -> Result<Self, CreationError> {
+        let open = || DeviceBuilder::new(DeviceMode::Tap).bridge(bridge).bring_up(true).open();
+        Ok(Self(open()?, open()?))
+    }
+
+    /// Returns MTU of both devices, read from sysfs (`/sys/class/net/<name>/mtu`), e.g. to check that
+    /// MTU set on one of them is propagated to the bridge, but not to the other one.
+    pub fn mtu_both(&self) -> IoResult<(u32, u32)> {
+        let mtu = |device: &Device| {
+            read_sysfs(&device.name, "mtu")?.parse().map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+        };
+        Ok((mtu(&self.0)?, mtu(&self.1)?))
     }
 }
 
-impl Read for Device {
-    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
-        self.file.read(buf)
+/// Interrupt (wakeup) coalescing parameters, see [Device::set_coalesce]. Zero disables given limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoalesceParams {
+    /// Delay of RX notification after frame is received, in microseconds.
+    pub rx_usecs: u32,
+    /// Maximal number of frames received before RX notification.
+    pub rx_frames: u32,
+    /// Delay of TX completion notification after frame is sent, in microseconds.
+    pub tx_usecs: u32,
+    /// Maximal number of frames sent before TX completion notification.
+    pub tx_frames: u32,
+}
+
+/// Receiver of read frames and sender of frames to write, see [Device::into_channels].
+pub type FrameChannels = (Receiver<Vec<u8>>, Sender<Vec<u8>>);
+
+/// Configuration of the device, applied at once by [Device::apply]. Fields set to `None` are not changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InterfaceConfig {
+    /// IPv4 address.
+    pub address: Option<Ipv4Addr>,
+    /// IPv4 netmask.
+    pub netmask: Option<Ipv4Addr>,
+    /// IPv4 broadcast address.
+    pub broadcast: Option<Ipv4Addr>,
+    /// Maximal transmission unit.
+    pub mtu: Option<u32>,
+    /// MAC address (tap only).
+    pub mac: Option<[u8; 6]>,
+    /// Bring the device up (`true`) or down (`false`).
+    pub up: Option<bool>,
+    /// Additional flags (`IFF_*`), which are set. Flags are never cleared.
+    pub flags: Option<libc::c_int>,
+}
+
+bitflags! {
+    /// Administrative flags of the device (`IFF_*`), see [Device::sysfs_flags].
+    pub struct DeviceFlags: u32 {
+        /// Device is up.
+        const UP = libc::IFF_UP as u32;
+        /// Broadcast address is valid.
+        const BROADCAST = libc::IFF_BROADCAST as u32;
+        /// Debugging is enabled.
+        const DEBUG = libc::IFF_DEBUG as u32;
+        /// Device is loopback.
+        const LOOPBACK = libc::IFF_LOOPBACK as u32;
+        /// Device is point-to-point link.
+        const POINTOPOINT = libc::IFF_POINTOPOINT as u32;
+        /// Trailers are avoided (unused).
+        const NOTRAILERS = libc::IFF_NOTRAILERS as u32;
+        /// Device is running (never set in sysfs flags).
+        const RUNNING = libc::IFF_RUNNING as u32;
+        /// ARP is not used.
+        const NOARP = libc::IFF_NOARP as u32;
+        /// All frames are received.
+        const PROMISC = libc::IFF_PROMISC as u32;
+        /// All multicast frames are received.
+        const ALLMULTI = libc::IFF_ALLMULTI as u32;
+        /// Device is master of load balancer.
+        const MASTER = libc::IFF_MASTER as u32;
+        /// Device is slave of load balancer.
+        const SLAVE = libc::IFF_SLAVE as u32;
+        /// Multicast is supported.
+        const MULTICAST = libc::IFF_MULTICAST as u32;
+        /// Media type can be selected.
+        const PORTSEL = libc::IFF_PORTSEL as u32;
+        /// Media type is selected automatically.
+        const AUTOMEDIA = libc::IFF_AUTOMEDIA as u32;
+        /// Addresses are lost when device goes down.
+        const DYNAMIC = libc::IFF_DYNAMIC as u32;
+    }
+}
+
+bitflags! {
+    /// Offloads (`TUN_F_*`), which userspace is able to handle, see [Device::set_offload].
+    pub struct Offload: u32 {
+        /// Frames may have unfinished transport checksum.
+        const CSUM = 0x01;
+        /// Frames may be TCP over IPv4 superframes (requires `CSUM`).
+        const TSO4 = 0x02;
+        /// Frames may be TCP over IPv6 superframes (requires `CSUM`).
+        const TSO6 = 0x04;
+        /// TCP superframes may have ECN congestion window reduced flag set.
+        const TSO_ECN = 0x08;
+        /// Frames may be UDP superframes (requires `CSUM`).
+        const UFO = 0x10;
+    }
+}
+
+bitflags! {
+    /// Events of the device file descriptor (`POLL*`), see [Device::as_pollfd] and [poll_devices].
+    pub struct PollEvents: libc::c_short {
+        /// Frame is available for read.
+        const READABLE = libc::POLLIN;
+        /// Frame can be written without blocking.
+        const WRITABLE = libc::POLLOUT;
+        /// Error occurred (reported only in results).
+        const ERROR = libc::POLLERR;
+        /// Device was removed (reported only in results).
+        const HANGUP = libc::POLLHUP;
+    }
+}
+
+/// Device ready for some events, returned by [poll_devices].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollResult {
+    /// Position of the device in the polled set.
+    pub index: usize,
+    /// Events, which occurred.
+    pub events: PollEvents,
+}
+
+/// Complete configuration of the device, which can be recreated from it, see [Device::to_config].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceConfig {
+    /// Name of the device.
+    pub name: String,
+    /// Mode of the device.
+    pub mode: DeviceMode,
+    /// Frames are prefixed with [PacketInfo].
+    pub packet_info: bool,
+    /// Frames are prefixed with [VirtioNetHeader].
+    pub vnet_hdr: bool,
+    /// Device is kept after its file descriptor is closed.
+    pub persistent: bool,
+    /// Configuration of the interface.
+    pub interface: InterfaceConfig,
+}
+
+impl DeviceConfig {
+    /// Open (or attach to) device with this configuration, and apply the interface configuration.
+    ///
+    /// # Errors
+    ///
+    /// Same as [DeviceBuilder::open] and [Device::apply].
+    pub fn open(&self) -> Result<Device, CreationError> {
+        let mut device = DeviceBuilder::new(self.mode)
+            .name(&self.name)
+            .packet_info(self.packet_info)
+            .vnet_hdr(self.vnet_hdr)
+            .open()?;
+        tun_set_persist(&device.file, self.persistent)?;
+        if self.persistent {
+            device.flags |= libc::IFF_PERSIST;
+        } else {
+            device.flags &= !libc::IFF_PERSIST;
+        }
+        device.apply(&self.interface)?;
+        Ok(device)
+    }
+}
+
+#[cfg(feature = "netlink")]
+bitflags! {
+    /// Flags controlling how XDP program is attached, see [Device::attach_xdp].
+    pub struct XdpFlags: u32 {
+        /// Fail, if some program is already attached.
+        const UPDATE_IF_NOEXIST = 1 << 0;
+        /// Generic XDP, running after socket buffer allocation. Supported by every device.
+        const SKB_MODE = 1 << 1;
+        /// Native XDP, running in device driver.
+        const DRV_MODE = 1 << 2;
+        /// Offloaded XDP, running on the network card.
+        const HW_MODE = 1 << 3;
+    }
+}
+
+#[cfg(feature = "netlink")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Hook, where XDP program is attached
+/// * `Generic` - Generic XDP, supported by every device (tun/tap included)
+/// * `Native` - XDP in device driver
+/// * `Offload` - XDP offloaded to the network card
+pub enum XdpMode {
+    Generic,
+    Native,
+    Offload,
+}
+
+#[cfg(feature = "netlink")]
+impl From<XdpMode> for XdpFlags {
+    fn from(mode: XdpMode) -> Self {
+        match mode {
+            XdpMode::Generic => XdpFlags::SKB_MODE,
+            XdpMode::Native => XdpFlags::DRV_MODE,
+            XdpMode::Offload => XdpFlags::HW_MODE,
+        }
+    }
+}
+
+#[cfg(feature = "netlink")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Event reported by [Device::poll_events]
+/// * `Readable` - Frame is available for reading
+/// * `LinkUp` - Device was brought up
+/// * `LinkDown` - Device was brought down
+/// * `Renamed` - Device was renamed, contains the new name
+pub enum DeviceEvent {
+    Readable,
+    LinkUp,
+    LinkDown,
+    Renamed(String),
+}
+
+#[cfg(feature = "netlink")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Entry of forwarding database (FDB) associated with the device, see [Device::fdb_list].
+pub struct FdbEntry {
+    /// MAC address.
+    pub mac: [u8; 6],
+    /// VLAN the entry belongs to, `None` for entries valid in all VLANs.
+    pub vlan: Option<u16>,
+    /// Entry belongs to the bridge the device is attached to (has `NDA_MASTER`), otherwise it is the
+    /// device's own address list entry (`NTF_SELF`, e.g. joined multicast group).
+    pub master: bool,
+    /// Entry is static or local (`NUD_NOARP` or `NUD_PERMANENT`) and does not age out.
+    pub permanent: bool,
+}
+
+/// Network tun or tap device, created with [DeviceBuilder].
+pub struct Device {
+    file: File,
+    mode: DeviceMode,
+    name: String,
+    packet_info: bool,
+    vnet_hdr: bool,
+    /// Size of virtio header (`TUNGETVNETHDRSZ`) read when device was opened, 0 if it is not enabled.
+    vnet_hdr_len: usize,
+    /// Flags of the device (`IFF_*`) read by `TUNGETIFF` when it was opened, see [Device::supports_multi_queue].
+    flags: libc::c_int,
+    membership: Option<File>,
+    stash: Option<Vec<u8>>,
+    /// Buffer holding frame returned by [Device::recv].
+    recv_buf: Vec<u8>,
+    strict_mtu: bool,
+    /// Frames read and written through this file descriptor, see [Device::queue_counters].
+    counters: QueueCounters,
+    /// Directions shut down by [Device::shutdown], reading and writing.
+    shut_read: bool,
+    shut_write: bool,
+    /// Policy for frames with invalid checksums, if they are verified, see [DeviceBuilder::validate_checksums].
+    #[cfg(feature = "etherparse")]
+    checksums: Option<ChecksumPolicy>,
+    /// Socket subscribed to link notifications and index of the device, see [Device::poll_events].
+    #[cfg(feature = "netlink")]
+    events: Option<(netlink::NetlinkSocket, u32)>,
+}
+
+impl Device {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn mode(&self) -> DeviceMode {
+        self.mode
+    }
+
+    /// Returns length of prefix preceding every frame read from and written to the device, which must
+    /// be skipped to get to the packet (tun) or ethernet header (tap).
+    ///
+    /// # Remarks
+    ///
+    /// Kernel places packet info ([PacketInfo], 4 bytes, if enabled by [DeviceBuilder::packet_info])
+    /// first, followed by virtio header ([VirtioNetHeader], if enabled by [DeviceBuilder::vnet_hdr]). Size of
+    /// virtio header is read from the device (`TUNGETVNETHDRSZ`) when it is opened or received, it is 10 bytes,
+    /// unless creator of the device changed it (`TUNSETVNETHDRSZ`), e.g. to 12 bytes for mergeable receive
+    /// buffers. So with both enabled the prefix is usually 14 bytes.
+    pub fn prefix_len(&self) -> usize {
+        self.packet_info_len() + self.vnet_hdr_len
+    }
+
+    /// Returns length of packet info preceding virtio header, 0 if packet info is not enabled.
+    fn packet_info_len(&self) -> usize {
+        if self.packet_info { PacketInfo::SIZE } else { 0 }
+    }
+
+    /// Returns user owning the device, or `None` if device is not owned by any user.
+    ///
+    /// # Remarks
+    ///
+    /// Value is read from sysfs (`/sys/class/net/<name>/owner`).
+    pub fn owner(&self) -> IoResult<Option<libc::uid_t>> {
+        self.read_id("owner")
+    }
+
+    /// Returns group owning the device, or `None` if device is not owned by any group.
+    ///
+    /// # Remarks
+    ///
+    /// Value is read from sysfs (`/sys/class/net/<name>/group`).
+    pub fn group(&self) -> IoResult<Option<libc::gid_t>> {
+        self.read_id("group")
+    }
+
+    /// Returns link layer type (`ARPHRD_*`) of the device.
+    ///
+    /// # Remarks
+    ///
+    /// Value is read from sysfs (`/sys/class/net/<name>/type`). Tun devices are created as
+    /// `ARPHRD_NONE` and tap devices as `ARPHRD_ETHER`, but type of tun device can be changed
+    /// (`TUNSETLINK`) while it is down. Value is returned as is, even if it does not match [Device::mode].
+    pub fn link_type(&self) -> IoResult<u16> {
+        read_sysfs(&self.name, "type")?.parse()
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Returns administrative flags of the device, read from sysfs (`/sys/class/net/<name>/flags`), so
+    /// no socket is opened, unlike for `SIOCGIFFLAGS`.
+    ///
+    /// # Remarks
+    ///
+    /// Flags are reported as kernel stores them, so [DeviceFlags::PROMISC] and [DeviceFlags::ALLMULTI]
+    /// reflect requests of all users (e.g. bridge or packet sockets), and operational state ([DeviceFlags::RUNNING])
+    /// is never set, see [Device::wait_running]. Unknown flags are ignored.
+    pub fn sysfs_flags(&self) -> IoResult<DeviceFlags> {
+        let flags = read_sysfs(&self.name, "flags")?;
+        u32::from_str_radix(flags.trim_start_matches("0x"), 16)
+            .map(DeviceFlags::from_bits_truncate)
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Returns index of the device, read from sysfs (`/sys/class/net/<name>/ifindex`), so no socket
+    /// is opened, unlike for `SIOCGIFINDEX`. Useful in unprivileged or sandboxed processes, where sysfs
+    /// is available, but sockets are not.
+    pub fn index_sysfs(&self) -> IoResult<u32> {
+        read_sysfs(&self.name, "ifindex")?.parse()
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Create monitor of drop counters of the device, sampling them every `interval`, see [DropMonitor].
+    pub fn drop_monitor(&self, interval: Duration) -> DropMonitor {
+        DropMonitor::new(&self.name, interval)
+    }
+
+    /// Returns sysfs state of each queue of the device, see [QueueStats].
+    ///
+    /// # Remarks
+    ///
+    /// Values are read from sysfs (`/sys/class/net/<name>/queues/rx-<index>` and `tx-<index>`).
+    /// Kernel creates entries only for queues, which are attached (see [Device::is_multi_queue]), and
+    /// exposes only transmit timeouts, byte queue limits and flow table size there, so these are not
+    /// traffic counters and cannot show how traffic is spread over queues. Values, which are missing, are
+    /// `None`. Queues are returned ordered by index. Frames and bytes of the queue of this descriptor are
+    /// counted by the crate, see [Device::queue_counters].
+    pub fn queue_stats(&self) -> IoResult<Vec<QueueStats>> {
+        queue_stats(&self.name)
+    }
+
+    /// Returns number of frames and bytes read and written through this file descriptor, i.e. traffic of single
+    /// queue of multi-queue device, see [QueueCounters].
+    ///
+    /// # Remarks
+    ///
+    /// Kernel does not expose per-queue frame and byte counters of tun/tap devices (see [Device::queue_stats]),
+    /// so they are counted by the crate, for frames read and written by any method of this device (including
+    /// [AsyncDevice](crate::unix::AsyncDevice) created from it). Counters belong to this descriptor only:
+    /// traffic of other processes using the queue is not counted, and duplicates of the descriptor (e.g.
+    /// [Device::dup_for_child]) have their own counters starting from zero, even though they share the queue.
+    pub fn queue_counters(&self) -> QueueCounters {
+        self.counters
+    }
+
+    fn read_id(&self, attribute: &str) -> IoResult<Option<u32>> {
+        let value: i64 = read_sysfs(&self.name, attribute)?.parse()
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))?;
+        Ok(if value < 0 { None } else { Some(value as u32) })
+    }
+
+    /// Wait until the device is running (`IFF_RUNNING`), i.e. it is up and has carrier.
+    /// Returns false, if device is not running after `timeout` elapses.
+    ///
+    /// # Remarks
+    ///
+    /// State is polled, starting with short interval, which doubles up to 200 ms after each check.
+    pub fn wait_running(&self, timeout: Duration) -> Result<bool, CreationError> {
+        let socket = get_control_socket()?;
+        let deadline = Instant::now() + timeout;
+        let mut interval = POLL_INTERVAL;
+        loop {
+            if get_flags(&socket, &self.name)? as libc::c_int & libc::IFF_RUNNING != 0 {
+                return Ok(true);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            thread::sleep(interval.min(deadline - now));
+            interval = (interval * 2).min(MAX_POLL_INTERVAL);
+        }
+    }
+
+    /// Returns true, if device was created with multiple queues (`IFF_MULTI_QUEUE`).
+    ///
+    /// # Remarks
+    ///
+    /// Flags are read from the device itself (`TUNGETIFF`), so result is correct also for device
+    /// attached by other process. Queues of single queue device cannot be enabled or disabled
+    /// (`TUNSETQUEUE` fails with `EINVAL`).
+    pub fn is_multi_queue(&self) -> Result<bool, CreationError> {
+        let flags = tun_get_interface(&self.file)?.get_fru().get_flags() as libc::c_int;
+        Ok(flags & libc::IFF_MULTI_QUEUE != 0)
+    }
+
+    /// Returns true, if frames are prefixed with [PacketInfo].
+    pub fn has_packet_info(&self) -> bool {
+        self.packet_info
+    }
+
+    /// Returns true, if frames are prefixed with [PacketInfo], according to flags of the device itself,
+    /// i.e. `IFF_NO_PI` is not set.
+    ///
+    /// # Remarks
+    ///
+    /// Packet info is setting of the device shared by all its descriptors, which is replaced whenever
+    /// any process attaches to the device, so setting of this device may not match the device any longer.
+    /// Live value is stored, so following reads and writes and [Device::has_packet_info] use it. Flags are
+    /// read with `TUNGETIFF`, but `IFF_NO_PI` is taken from sysfs (`tun_flags`), as kernel reports `IFF_NOFILTER`,
+    /// which has the same value.
+    pub fn packet_info_active(&mut self) -> Result<bool, CreationError> {
+        let flags = tun_get_flags(&self.file)?;
+        self.packet_info = flags & libc::IFF_NO_PI == 0;
+        self.flags = flags;
+        Ok(self.packet_info)
+    }
+
+    /// Returns true, if frames are prefixed with [VirtioNetHeader].
+    pub fn has_vnet_hdr(&self) -> bool {
+        self.vnet_hdr
+    }
+
+    /// Returns true, if device has multiple queues (`IFF_MULTI_QUEUE`).
+    ///
+    /// # Remarks
+    ///
+    /// Unlike [Device::is_multi_queue], flags read when the device was opened are used, so no ioctl is issued.
+    /// Devices can be attached only with matching queue mode, so flags cannot change while the device is open.
+    pub fn supports_multi_queue(&self) -> bool {
+        self.flags & libc::IFF_MULTI_QUEUE != 0
+    }
+
+    /// Returns true, if device is kept after its last file descriptor is closed (`IFF_PERSIST`).
+    ///
+    /// # Remarks
+    ///
+    /// Flags read when the device was opened are used, updated when persistence is set by [DeviceConfig::open].
+    /// Change made by other process (e.g. `ip tuntap`) is not reflected.
+    pub fn is_persistent(&self) -> bool {
+        self.flags & libc::IFF_PERSIST != 0
+    }
+
+    /// Read single frame, split into its packet info and owned payload.
+    ///
+    /// # Remarks
+    ///
+    /// Packet info and payload are read with single vectored read, directly into their final
+    /// location, so payload is never copied. Payload vector is allocated for the largest frame
+    /// allowed by current MTU of the device (read by ioctl), or for 64 KiB superframe if virtio
+    /// header is enabled, and truncated to the frame, so its capacity may exceed its length.
+    ///
+    /// # Errors
+    ///
+    /// If device was not opened with packet info, `InvalidInput` error is returned.
+    /// If frame is shorter than packet info, `InvalidData` error is returned.
+    pub fn read_packet_owned(&mut self) -> IoResult<(PacketInfo, Vec<u8>)> {
+        if !self.packet_info {
+            return Err(IoError::new(ErrorKind::InvalidInput, "device does not have packet info enabled"));
+        }
+        let mut info = [0u8; PacketInfo::SIZE];
+        let mut payload = vec![0u8; self.max_payload_len()?];
+        let read = self.read_frame(&mut [IoSliceMut::new(&mut info), IoSliceMut::new(&mut payload)])?;
+        let info = PacketInfo::parse(&info[..read.min(PacketInfo::SIZE)])
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidData, "frame is shorter than packet info"))?;
+        payload.truncate(read - PacketInfo::SIZE);
+        Ok((info, payload))
+    }
+
+    /// Size of the largest frame without packet info (but with virtio header), which can be read
+    /// with current MTU of the device.
+    fn max_payload_len(&self) -> IoResult<usize> {
+        if self.vnet_hdr {
+            return Ok(self.vnet_hdr_len + MAX_FRAME_SIZE);
+        }
+        let mtu = get_control_socket()
+            .and_then(|socket| get_mtu(&socket, &self.name))
+            .map_err(IoError::other)? as usize;
+        Ok(match self.mode {
+            DeviceMode::Tun => mtu,
+            DeviceMode::Tap => mtu + libc::ETH_HLEN as usize + VLAN_TAG_LEN,
+        })
+    }
+
+    /// Switch device to non-blocking mode, in which reads return `WouldBlock` error instead of
+    /// waiting for a frame.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> IoResult<()> {
+        set_nonblocking(&self.file, nonblocking)
+    }
+
+    /// Returns `pollfd` structure with file descriptor of the device and requested `events`, for use
+    /// with `poll`/`ppoll` together with other descriptors.
+    ///
+    /// # Remarks
+    ///
+    /// Frame buffered by [Device::peek] is not visible to `poll`, see [poll_devices], which accounts for it.
+    pub fn as_pollfd(&self, events: PollEvents) -> libc::pollfd {
+        libc::pollfd { fd: self.file.as_raw_fd(), events: events.bits(), revents: 0 }
+    }
+
+    /// Returns length of transmit queue (`txqueuelen`) of the device, see [DeviceBuilder::txqueuelen].
+    pub fn tx_queue_len(&self) -> Result<u32, CreationError> {
+        Ok(get_tx_queue_len(&get_control_socket()?, &self.name)? as u32)
+    }
+
+    /// Check whether payload (IP packet) of `payload_len` bytes fits into the MTU of the device.
+    ///
+    /// # Errors
+    ///
+    /// If payload is larger than MTU, [CreationError::PayloadTooLarge](crate::error::CreationError) is returned.
+    /// If MTU cannot be read, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    pub fn validate_mtu_against_payload(&self, payload_len: usize) -> Result<(), CreationError> {
+        let mtu = get_mtu(&get_control_socket()?, &self.name)? as usize;
+        if payload_len > mtu {
+            return Err(CreationError::PayloadTooLarge { len: payload_len, mtu });
+        }
+        Ok(())
+    }
+
+    /// Set MAC address of tap device, checking that it is not used by other host on the same segment.
+    ///
+    /// # Remarks
+    ///
+    /// Conflict detection is best effort. If the device is a bridge port, address is looked up in the
+    /// forwarding database of the bridge, which contains only addresses of hosts, which recently sent
+    /// a frame. Otherwise the segment is behind the device file descriptor (i.e. in the application)
+    /// and conflict cannot be detected. After the address is set, it is read back to confirm that kernel
+    /// accepted it.
+    ///
+    /// # Errors
+    ///
+    /// If address is multicast (or broadcast), [CreationError::InvalidMacAddress](crate::error::CreationError) is returned.
+    /// If address is used on the bridged segment, [CreationError::MacAddressInUse](crate::error::CreationError) is returned.
+    /// If address read back differs, [CreationError::IoctlError](crate::error::CreationError) with `EIO` is returned.
+    pub fn set_mac_checked(&self, mac: [u8; 6]) -> Result<(), CreationError> {
+        if mac[0] & 0x01 != 0 {
+            return Err(CreationError::InvalidMacAddress);
+        }
+        if bridge_learned(&self.name, mac)? {
+            return Err(CreationError::MacAddressInUse);
+        }
+        let socket = get_control_socket()?;
+        set_hw_address(&socket, &InterfaceRequest::new(&self.name, InterfaceFieldReplaceUnit::hw_address(mac))?)?;
+        if get_hw_address(&socket, &self.name)? != mac {
+            return Err(nix::Error::Sys(Errno::EIO).into());
+        }
+        Ok(())
+    }
+
+    /// Set if `write` should check frames against MTU of the device, see [Device::validate_mtu_against_payload].
+    ///
+    /// # Remarks
+    ///
+    /// Payload is the frame without [PacketInfo] and ethernet header (tap), VLAN tagged frames are
+    /// therefore accepted only if they are 4 bytes below the limit. In strict mode MTU is read before
+    /// each write, which costs an additional syscall.
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, `write` of frame exceeding the MTU fails with `InvalidInput` error, containing
+    /// [CreationError::PayloadTooLarge](crate::error::CreationError), instead of being written.
+    pub fn set_strict_mtu(&mut self, strict: bool) {
+        self.strict_mtu = strict;
+    }
+
+    /// Length of framing (packet info, virtio header and link layer header) preceding the payload of the frame.
+    fn framing_len(&self) -> usize {
+        let mut len = self.prefix_len();
+        if self.mode == DeviceMode::Tap {
+            len += libc::ETH_HLEN as usize;
+        }
+        len
+    }
+
+    /// Program RX flow hash indirection table (`ETHTOOL_SRXFHINDIR`), like `ethtool -X <name> weight ...`.
+    /// Each entry of `indir` is index of the queue receiving flows hashed to that entry, empty table
+    /// restores the default spreading of flows across all queues.
+    ///
+    /// # Remarks
+    ///
+    /// Table must have exactly the size reported by the driver and queue indices must be lower than
+    /// number of its RX queues. Indirection table is an alternative to eBPF steering program for
+    /// multi-queue devices.
+    ///
+    /// # Errors
+    ///
+    /// If driver does not support indirection table (tun/tap driver of current kernels does not),
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EOPNOTSUPP` is returned. Table of
+    /// wrong size or with invalid queue index is refused with `EINVAL`.
+    pub fn set_rxfh(&self, indir: &[u32]) -> Result<(), CreationError> {
+        set_rxfh_indir(&get_control_socket()?, &self.name, indir)
+    }
+
+    /// Returns names and values of extended statistics of the device, like `ethtool -S <name>`
+    /// (`ETHTOOL_GSTRINGS` and `ETHTOOL_GSTATS`).
+    ///
+    /// # Remarks
+    ///
+    /// Statistics are driver specific, tun/tap driver does not provide any, so empty vector is returned
+    /// for plain tun/tap devices. For basic counters, see [Device::drop_monitor] or sysfs statistics.
+    ///
+    /// # Errors
+    ///
+    /// If request fails, [CreationError::IoctlError](crate::error::CreationError) is returned. Missing
+    /// ethtool support of the driver (`EOPNOTSUPP`) is not an error.
+    pub fn ethtool_stats(&self) -> Result<Vec<(String, u64)>, CreationError> {
+        match ethtool_stats(&get_control_socket()?, &self.name) {
+            Err(CreationError::IoctlError(nix::Error::Sys(Errno::EOPNOTSUPP))) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    /// Set interrupt coalescing parameters of the device (`ETHTOOL_SCOALESCE`), like
+    /// `ethtool -C <name> rx-usecs ... rx-frames ...`, trading latency for batching.
+    ///
+    /// # Remarks
+    ///
+    /// Current parameters are read first, so other parameters of the driver are kept. Tun/tap driver
+    /// supports only `rx_frames`, which sets number of frames written to the device and passed to the
+    /// network stack as a batch (limited to 64). Applied parameters can be read back by [Device::coalesce].
+    ///
+    /// # Errors
+    ///
+    /// If driver does not support some non-zero parameter (e.g. any other than `rx_frames` for tun/tap),
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EOPNOTSUPP` is returned.
+    pub fn set_coalesce(&self, params: CoalesceParams) -> Result<(), CreationError> {
+        let socket = get_control_socket()?;
+        let mut fields = get_coalesce(&socket, &self.name)?;
+        fields[0] = params.rx_usecs;
+        fields[1] = params.rx_frames;
+        fields[4] = params.tx_usecs;
+        fields[5] = params.tx_frames;
+        set_coalesce(&socket, &self.name, &fields)
+    }
+
+    /// Returns interrupt coalescing parameters of the device (`ETHTOOL_GCOALESCE`), see [Device::set_coalesce].
+    pub fn coalesce(&self) -> Result<CoalesceParams, CreationError> {
+        let fields = get_coalesce(&get_control_socket()?, &self.name)?;
+        Ok(CoalesceParams {
+            rx_usecs: fields[0],
+            rx_frames: fields[1],
+            tx_usecs: fields[4],
+            tx_frames: fields[5],
+        })
+    }
+
+    /// Set send buffer size of the device (`TUNSETSNDBUF`).
+    ///
+    /// # Remarks
+    ///
+    /// Buffer limits memory of frames written to the device, which were not yet processed by the kernel,
+    /// including kernel overhead of each frame. By default the size is unlimited.
+    pub fn set_sndbuf(&self, size: libc::c_int) -> Result<(), CreationError> {
+        tun_set_sndbuf(&self.file, size)
+    }
+
+    /// Set send buffer size to the bandwidth-delay product of the link, see [Device::set_sndbuf].
+    ///
+    /// # Returns
+    ///
+    /// Size of the send buffer, which was set.
+    ///
+    /// # Remarks
+    ///
+    /// Size is `bandwidth_bps * rtt / 8` bytes, clamped between 64 KiB (which holds at least one
+    /// frame of maximal size) and `c_int::MAX`.
+    pub fn tune_sndbuf(&mut self, bandwidth_bps: u64, rtt: Duration) -> Result<usize, CreationError> {
+        let size = sndbuf_size(bandwidth_bps, rtt);
+        self.set_sndbuf(size)?;
+        Ok(size as usize)
+    }
+
+    /// Prepare device to be inherited by child process, e.g. privilege separated worker, by clearing
+    /// close-on-exec flag (`FD_CLOEXEC`) of its file descriptor.
+    ///
+    /// # Returns
+    ///
+    /// Raw file descriptor, which child adopts with `from_raw_fd` (e.g. `File::from_raw_fd`).
+    ///
+    /// # Remarks
+    ///
+    /// Descriptor is closed when device is dropped, so parent must keep the device alive until the
+    /// child is spawned, or give up the ownership with [IntoRawFd::into_raw_fd]. Every process
+    /// spawned in the meantime inherits the descriptor too.
+    pub fn prepare_for_exec(&self) -> IoResult<RawFd> {
+        set_cloexec(&self.file, false)?;
+        Ok(self.file.as_raw_fd())
+    }
+
+    /// Returns what happens with the device file descriptor, if process forks now.
+    ///
+    /// # Remarks
+    ///
+    /// After fork (without exec), parent and child share the same open file, so every frame is read
+    /// by only one of them, whichever reads first, and writes of both processes are interleaved by
+    /// whole frames. Device state held in memory is duplicated, including frame buffered by [Device::peek],
+    /// which is then returned in both processes, and promiscuous and all-multicast memberships, which
+    /// are released when the last copy is closed. Use [Device::dup_for_child] to get separate device
+    /// intended for the child.
+    pub fn prepare_for_fork(&self) -> ForkDisposition {
+        // Reading descriptor flags fails only for invalid descriptor, which device never holds
+        match get_cloexec(&self.file) {
+            Ok(false) => ForkDisposition::Inherited,
+            _ => ForkDisposition::CloseOnExec,
+        }
+    }
+
+    /// Duplicate the device file descriptor (`dup`) for intentional inheritance by child process.
+    /// Unlike `File::try_clone`, close-on-exec flag of the duplicate is cleared, so it is kept
+    /// also after exec.
+    ///
+    /// # Remarks
+    ///
+    /// Duplicate refers to the same open file, see [Device::prepare_for_fork] for sharing semantics.
+    /// It does not take over frame buffered by [Device::peek], promiscuous and all-multicast memberships
+    /// or strict MTU mode.
+    pub fn dup_for_child(&self) -> IoResult<Device> {
+        let fd = unsafe { libc::dup(self.file.as_raw_fd()) };
+        if fd < 0 {
+            return Err(IoError::last_os_error());
+        }
+        Ok(self.with_file(unsafe { File::from_raw_fd(fd) }))
+    }
+
+    /// Create device sharing the same open file `file` (duplicate of the descriptor), see [Device::dup_for_child].
+    fn with_file(&self, file: File) -> Device {
+        Device {
+            file,
+            mode: self.mode,
+            name: self.name.clone(),
+            packet_info: self.packet_info,
+            vnet_hdr: self.vnet_hdr,
+            vnet_hdr_len: self.vnet_hdr_len,
+            flags: self.flags,
+            membership: None,
+            stash: None,
+            recv_buf: Vec::new(),
+            strict_mtu: false,
+            counters: QueueCounters::default(),
+            shut_read: false,
+            shut_write: false,
+            #[cfg(feature = "etherparse")]
+            checksums: self.checksums,
+            #[cfg(feature = "netlink")]
+            events: None,
+        }
+    }
+
+    /// Shut down reading, writing or both directions of the device, so following reads or writes fail with
+    /// `NotConnected` error, e.g. to stop writing while frames already queued are still read.
+    ///
+    /// # Remarks
+    ///
+    /// Tun file descriptor does not support `shutdown(2)`, so direction is only marked in this device,
+    /// descriptors duplicated from it (e.g. by [Device::dup_for_child]) are not affected. Descriptor is
+    /// not closed, device keeps receiving frames, until the device is dropped. Shutdown cannot be undone.
+    /// Frames buffered by [Device::peek] are not returned after reading is shut down.
+    pub fn shutdown(&mut self, how: Shutdown) {
+        match how {
+            Shutdown::Read => self.shut_read = true,
+            Shutdown::Write => self.shut_write = true,
+            Shutdown::Both => {
+                self.shut_read = true;
+                self.shut_write = true;
+            }
+        }
+    }
+
+    /// Quiesce the device before it is closed: bring it down, optionally discard frames waiting for read
+    /// (`drain`), and clear persistence (`TUNSETPERSIST`), so device is removed when its last descriptor is closed.
+    ///
+    /// # Remarks
+    ///
+    /// Device is brought down first, so no new frames are queued while draining. Draining is best effort, it
+    /// stops at the first read error and is skipped if reading was shut down by [Device::shutdown]. Addresses and
+    /// routes of the device are removed by kernel together with the device. Calling it again does nothing,
+    /// device removed in the meantime is not an error.
+    ///
+    /// # Errors
+    ///
+    /// If device cannot be brought down or its persistence cleared,
+    /// [CreationError::IoctlError](crate::error::CreationError) is returned.
+    pub fn quiesce(&mut self, drain: bool) -> Result<(), CreationError> {
+        match self.update_flags(libc::IFF_UP, 0) {
+            Err(CreationError::DeviceGone) => return Ok(()),
+            result => result?,
+        }
+        if drain && !self.shut_read {
+            let mut buf = vec![0u8; MAX_FRAME_SIZE];
+            while let Ok(Some(_)) = self.read_deadline(&mut buf, Instant::now()) {}
+        }
+        match tun_set_persist(&self.file, false) {
+            Err(CreationError::DeviceGone) => (),
+            result => result?,
+        }
+        self.flags &= !libc::IFF_PERSIST;
+        Ok(())
+    }
+
+    /// Returns `NotConnected` error, if given direction (read or write) was shut down by [Device::shutdown].
+    pub(crate) fn check_shutdown(&self, direction: Shutdown) -> IoResult<()> {
+        match direction {
+            Shutdown::Read if self.shut_read => {
+                Err(IoError::new(ErrorKind::NotConnected, "device is shut down for reading"))
+            }
+            Shutdown::Write if self.shut_write => {
+                Err(IoError::new(ErrorKind::NotConnected, "device is shut down for writing"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Send file descriptor of the device over unix socket (`SCM_RIGHTS`), e.g. from privileged process,
+    /// which opened the device, to unprivileged worker, which receives it by [Device::recv_over].
+    ///
+    /// # Remarks
+    ///
+    /// Both processes share the same open file description (like with `dup`), so the device is removed (unless
+    /// it is persistent) only after both close it.
+    pub fn send_over(&self, sock: &UnixStream) -> IoResult<()> {
+        send_fd(sock, self.file.as_raw_fd())
+    }
+
+    /// Receive file descriptor of the device sent by [Device::send_over].
+    ///
+    /// # Arguments
+    ///
+    /// * `sock` - Unix socket connected to the sender.
+    /// * `mode` - Mode of the device.
+    /// * `name` - Name of the device, used by its configuration methods.
+    ///
+    /// # Remarks
+    ///
+    /// Whether the device has packet info and virtio header is read from the received descriptor (`TUNGETIFF`).
+    ///
+    /// # Errors
+    ///
+    /// If received descriptor is not attached to tun/tap device, or `mode` does not match the device,
+    /// `InvalidInput` error is returned.
+    pub fn recv_over(sock: &UnixStream, mode: DeviceMode, name: String) -> IoResult<Device> {
+        let file = recv_fd(sock)?;
+        let invalid = |e| IoError::new(ErrorKind::InvalidInput, e);
+        let flags = tun_get_flags(&file).map_err(invalid)?;
+        let tun = flags & libc::IFF_TUN != 0;
+        if tun != (mode == DeviceMode::Tun) {
+            return Err(IoError::new(ErrorKind::InvalidInput, format!("device is not in {} mode", mode)));
+        }
+        Device::from_file(file, mode, name, flags).map_err(invalid)
+    }
+
+    /// Create device from file descriptor attached to tun/tap device with `flags` (`IFF_*`).
+    fn from_file(file: File, mode: DeviceMode, name: String, flags: libc::c_int) -> Result<Device, CreationError> {
+        let vnet_hdr = flags & libc::IFF_VNET_HDR != 0;
+        Ok(Device {
+            vnet_hdr_len: vnet_hdr_len(&file, vnet_hdr)?,
+            file,
+            mode,
+            name,
+            packet_info: flags & libc::IFF_NO_PI == 0,
+            vnet_hdr,
+            flags,
+            membership: None,
+            stash: None,
+            recv_buf: Vec::new(),
+            strict_mtu: false,
+            counters: QueueCounters::default(),
+            shut_read: false,
+            shut_write: false,
+            #[cfg(feature = "etherparse")]
+            checksums: None,
+            #[cfg(feature = "netlink")]
+            events: None,
+        })
+    }
+
+    /// Write ethernet frame, padded with zeros to the minimal ethernet frame size (60 bytes), as
+    /// some bridges and switches drop shorter (runt) frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Ethernet frame, prefixed with [PacketInfo] and virtio header, if they are enabled.
+    ///
+    /// # Remarks
+    ///
+    /// Bridge or real network card may pad the frame on its own, so this is just a precaution.
+    /// Frames, which are already long enough, are written unchanged.
+    ///
+    /// # Errors
+    ///
+    /// If device is not tap device, `InvalidInput` error is returned.
+    pub fn write_padded(&mut self, frame: &[u8]) -> IoResult<usize> {
+        if self.mode != DeviceMode::Tap {
+            return Err(IoError::new(ErrorKind::InvalidInput, "only ethernet frames of tap device can be padded"));
+        }
+        let min_size = MIN_ETHERNET_FRAME_SIZE + self.prefix_len();
+        if frame.len() >= min_size {
+            return self.write(frame);
+        }
+        let mut padded = frame.to_vec();
+        padded.resize(min_size, 0);
+        self.write(&padded)
+    }
+
+    /// Write batch of frames, each element as exactly one frame (prefixed with packet info and virtio
+    /// header, if enabled), in order.
+    ///
+    /// # Returns
+    ///
+    /// Number of frames written. If some frame cannot be written, writing stops and number of frames
+    /// written before it is returned, so the caller can retry the rest, like with partial write.
+    /// Interrupted writes are retried.
+    ///
+    /// # Errors
+    ///
+    /// If the first frame cannot be written, its error is returned, e.g. `WouldBlock` in non-blocking mode.
+    pub fn write_all_frames(&mut self, frames: &[Vec<u8>]) -> IoResult<usize> {
+        let mut written = 0;
+        while written < frames.len() {
+            match self.write(&frames[written]) {
+                Ok(_) => written += 1,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) if written == 0 => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(written)
+    }
+
+    /// Insert 802.1Q tag into untagged ethernet frame and write it to tap device.
+    ///
+    /// # Arguments
+    ///
+    /// * `vid` - VLAN identifier (0-4094).
+    /// * `pcp` - Priority code point (0-7).
+    /// * `inner` - Untagged ethernet frame, without packet info and virtio header, which are prepended
+    ///   (packet info with 802.1Q protocol and empty virtio header), if enabled.
+    ///
+    /// # Returns
+    ///
+    /// Number of written bytes, including the tag.
+    ///
+    /// # Errors
+    ///
+    /// If device is not in tap mode, `vid` or `pcp` is out of range, or `inner` is shorter than ethernet header,
+    /// `InvalidInput` error is returned.
+    pub fn send_vlan(&mut self, vid: u16, pcp: u8, inner: &[u8]) -> IoResult<usize> {
+        if self.mode != DeviceMode::Tap {
+            return Err(IoError::new(ErrorKind::InvalidInput, "only ethernet frames of tap device can be tagged"));
+        }
+        if vid > VlanTag::MAX_VID || pcp > 7 {
+            return Err(IoError::new(ErrorKind::InvalidInput, "VLAN identifier or priority out of range"));
+        }
+        if inner.len() < libc::ETH_HLEN as usize {
+            return Err(IoError::new(ErrorKind::InvalidInput, "frame is shorter than ethernet header"));
+        }
+        self.check_shutdown(Shutdown::Write)?;
+        let tag = VlanTag { tpid: libc::ETH_P_8021Q as u16, pcp, dei: false, vid }.to_bytes();
+        let mut prefix = vec![0u8; self.prefix_len()];
+        if self.packet_info {
+            let info = PacketInfo { flags: 0, proto: libc::ETH_P_8021Q as u16 };
+            prefix[..PacketInfo::SIZE].copy_from_slice(&info.to_bytes());
+        }
+        let bufs = [IoSlice::new(&prefix), IoSlice::new(&inner[..12]), IoSlice::new(&tag), IoSlice::new(&inner[12..])];
+        let written = self.file.write_vectored(&bufs)?;
+        self.counters.count_written(written);
+        if written != prefix.len() + inner.len() + VlanTag::SIZE {
+            return Err(IoError::new(ErrorKind::WriteZero, "frame was not written completely"));
+        }
+        Ok(written - prefix.len())
+    }
+
+    /// Read single ethernet frame from tap device and remove its VLAN tag (802.1Q or 802.1ad), if it has any.
+    ///
+    /// # Returns
+    ///
+    /// Removed VLAN tag, or `None` for untagged frame, and untagged frame without packet info and virtio header.
+    ///
+    /// # Errors
+    ///
+    /// If device is not in tap mode, `InvalidInput` error is returned.
+    pub fn recv_vlan(&mut self) -> IoResult<(Option<VlanTag>, Vec<u8>)> {
+        if self.mode != DeviceMode::Tap {
+            return Err(IoError::new(ErrorKind::InvalidInput, "only tap device reads ethernet frames"));
+        }
+        let prefix = self.prefix_len();
+        let mut frame = self.recv()?.get(prefix..).unwrap_or(&[]).to_vec();
+        let tag = VlanTag::parse(&frame);
+        if tag.is_some() {
+            frame.drain(12..12 + VlanTag::SIZE);
+        }
+        Ok((tag, frame))
+    }
+
+    /// Read beginning of the next frame into `buf`, without consuming it.
+    ///
+    /// # Returns
+    ///
+    /// Number of bytes copied into `buf`, which is at most the length of the frame.
+    ///
+    /// # Remarks
+    ///
+    /// Tun file descriptor does not support `MSG_PEEK`, so frame is read into one-frame lookahead
+    /// buffer held by the device. Next `read` (or any other read method) returns the buffered frame,
+    /// before reading from the device. Repeated peeks return the same frame.
+    /// If device is converted into `AsyncDevice`, buffered frame is still returned by the first read,
+    /// but it does not wake up pending read tasks.
+    pub fn peek(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.check_shutdown(Shutdown::Read)?;
+        if self.stash.is_none() {
+            let mut frame = vec![0u8; MAX_FRAME_SIZE + self.prefix_len()];
+            let read = self.file.read(&mut frame)?;
+            frame.truncate(read);
+            self.stash = Some(frame);
+        }
+        let frame = self.stash.as_ref().unwrap();
+        let len = frame.len().min(buf.len());
+        buf[..len].copy_from_slice(&frame[..len]);
+        Ok(len)
+    }
+
+    /// Read single frame into buffer held by the device, without copying it out.
+    ///
+    /// # Returns
+    ///
+    /// Frame (including packet info and virtio header, if enabled), borrowed until the next `recv`.
+    ///
+    /// # Remarks
+    ///
+    /// Buffer is allocated by the first call and reused after. It is sized for the largest frame
+    /// (64 KiB and prefix, see [Device::prefix_len]), so frames are never truncated, regardless of
+    /// MTU of the device and virtio superframes.
+    pub fn recv(&mut self) -> IoResult<&[u8]> {
+        let size = MAX_FRAME_SIZE + self.prefix_len();
+        let mut buf = std::mem::take(&mut self.recv_buf);
+        buf.resize(size, 0);
+        let result = self.read_frame(&mut [IoSliceMut::new(&mut buf)]);
+        self.recv_buf = buf;
+        Ok(&self.recv_buf[..result?])
+    }
+
+    /// Read single frame into `bufs`, taking the frame buffered by [Device::peek] first.
+    /// Like the kernel, frame which does not fit into `bufs` is truncated.
+    fn read_frame(&mut self, bufs: &mut [IoSliceMut]) -> IoResult<usize> {
+        self.check_shutdown(Shutdown::Read)?;
+        loop {
+            let frame = match self.stash.take() {
+                Some(frame) => frame,
+                None if !self.validates_checksums() => {
+                    let read = self.file.read_vectored(bufs)?;
+                    self.counters.count_read(read);
+                    return Ok(read);
+                }
+                None => {
+                    let mut frame = vec![0u8; MAX_FRAME_SIZE + self.prefix_len()];
+                    let read = self.file.read(&mut frame)?;
+                    frame.truncate(read);
+                    frame
+                }
+            };
+            if !self.accept_frame(&frame)? {
+                continue;
+            }
+            let mut rest = &frame[..];
+            for buf in bufs.iter_mut() {
+                let len = rest.len().min(buf.len());
+                buf[..len].copy_from_slice(&rest[..len]);
+                rest = &rest[len..];
+            }
+            self.counters.count_read(frame.len());
+            return Ok(frame.len() - rest.len());
+        }
+    }
+
+    #[cfg(feature = "etherparse")]
+    fn validates_checksums(&self) -> bool {
+        self.checksums.is_some()
+    }
+
+    #[cfg(not(feature = "etherparse"))]
+    fn validates_checksums(&self) -> bool {
+        false
+    }
+
+    /// Verify checksums of the frame according to checksum policy.
+    /// Returns false, if frame should be dropped.
+    #[cfg(feature = "etherparse")]
+    fn accept_frame(&self, frame: &[u8]) -> IoResult<bool> {
+        let policy = match self.checksums {
+            Some(policy) => policy,
+            None => return Ok(true),
+        };
+        let packet = frame.get(self.prefix_len()..).unwrap_or(&[]);
+        if verify_checksums(packet, self.mode).ok() != Some(ChecksumStatus::Invalid) {
+            return Ok(true);
+        }
+        match policy {
+            ChecksumPolicy::Drop => {
+                #[cfg(feature = "log")]
+                log::debug!("{}: dropping frame of {} bytes with invalid checksum", self.name, frame.len());
+                Ok(false)
+            }
+            ChecksumPolicy::Error => Err(IoError::new(ErrorKind::InvalidData, ChecksumError)),
+        }
+    }
+
+    #[cfg(not(feature = "etherparse"))]
+    fn accept_frame(&self, _frame: &[u8]) -> IoResult<bool> {
+        Ok(true)
+    }
+
+    /// Returns iterator over frames, which skips frames that cannot be decoded, instead of ending.
+    ///
+    /// # Remarks
+    ///
+    /// Frames are read into buffer held by the device (see [Device::recv]) and copied into vectors of
+    /// exactly their size.
+    /// Frames shorter than [PacketInfo] prefix (if device has packet info enabled), frames with invalid
+    /// checksum (with `ChecksumPolicy::Error`) and reads interrupted by signal are skipped, and logged with
+    /// `log` feature enabled. Iterator ends on any other error, including `WouldBlock` in non-blocking mode.
+    pub fn frames_lossy(&mut self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        std::iter::from_fn(move || loop {
+            let frame = match self.recv() {
+                Ok(frame) => frame.to_vec(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                #[cfg(feature = "etherparse")]
+                Err(e) if e.get_ref().is_some_and(|inner| inner.is::<ChecksumError>()) => {
+                    #[cfg(feature = "log")]
+                    log::warn!("{}: skipping frame with invalid checksum", self.name);
+                    continue;
+                }
+                Err(_e) => {
+                    #[cfg(feature = "log")]
+                    log::debug!("{}: frames ended: {}", self.name, _e);
+                    return None;
+                }
+            };
+            if self.packet_info && frame.len() < PacketInfo::SIZE {
+                #[cfg(feature = "log")]
+                log::warn!("{}: skipping frame of {} bytes, shorter than packet info", self.name, frame.len());
+                continue;
+            }
+            return Some(frame);
+        })
+    }
+
+    /// Move the device into pair of channels, served by reader and writer thread.
+    ///
+    /// # Returns
+    ///
+    /// Receiver of frames read from the device and sender of frames to write to the device. Frames include
+    /// packet info and virtio header, if enabled.
+    ///
+    /// # Remarks
+    ///
+    /// Receiver is bounded (256 frames). If it is full, reader thread stops reading, so frames are queued
+    /// by the kernel and dropped when its queue (`txqueuelen`) overflows, like with slow reader of the device.
+    /// Sender is unbounded, as writes to the device do not block; frames are dropped by the kernel instead,
+    /// if network stack cannot keep up.
+    ///
+    /// Writer thread ends when all senders are dropped, or on write error. Reader thread ends on read error,
+    /// or with the next read frame after receiver is dropped, as blocking read cannot be interrupted earlier.
+    /// Device is closed after both threads end, its descriptors are not inherited by child processes.
+    /// Errors are logged with `log` feature enabled.
+    pub fn into_channels(mut self) -> IoResult<FrameChannels> {
+        let mut writer = self.with_file(self.file.try_clone()?);
+        let (read_tx, read_rx) = sync_channel(CHANNEL_CAPACITY);
+        let (write_tx, write_rx) = channel::<Vec<u8>>();
+        let mut buf = vec![0u8; MAX_FRAME_SIZE + self.prefix_len()];
+        thread::spawn(move || loop {
+            let frame = match self.read(&mut buf) {
+                Ok(read) => buf[..read].to_vec(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_e) => {
+                    #[cfg(feature = "log")]
+                    log::debug!("{}: reader thread ended: {}", self.name, _e);
+                    return;
+                }
+            };
+            if read_tx.send(frame).is_err() {
+                return;
+            }
+        });
+        thread::spawn(move || {
+            for frame in write_rx {
+                if let Err(_e) = writer.write_all(&frame) {
+                    #[cfg(feature = "log")]
+                    log::debug!("{}: writer thread ended: {}", writer.name, _e);
+                    return;
+                }
+            }
+        });
+        Ok((read_rx, write_tx))
+    }
+
+    /// Returns iterator over IP packets read from tun device, with their source and destination address.
+    ///
+    /// # Returns
+    ///
+    /// Source address, destination address and the whole packet, without packet info and virtio header.
+    ///
+    /// # Remarks
+    ///
+    /// Iterator never ends, each read error is yielded, reads interrupted by signal are retried.
+    /// Packets which cannot be parsed (other protocols than IPv4 and IPv6, truncated headers) are yielded
+    /// as `InvalidData` error containing [ParseError](crate::error::ParseError), they do not end the iteration.
+    ///
+    /// # Errors
+    ///
+    /// If device is not in tun mode, `InvalidInput` error is returned.
+    #[cfg(feature = "etherparse")]
+    pub fn ip_packets(&mut self) -> IoResult<impl Iterator<Item = IoResult<(IpAddr, IpAddr, Vec<u8>)>> + '_> {
+        if self.mode != DeviceMode::Tun {
+            return Err(IoError::new(ErrorKind::InvalidInput, "device is not in tun mode"));
+        }
+        Ok(std::iter::from_fn(move || loop {
+            let prefix = self.prefix_len();
+            let frame = match self.recv() {
+                Ok(frame) => frame,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            let packet = frame.get(prefix..).unwrap_or(&[]);
+            return Some(match ip_addresses(packet) {
+                Ok((source, destination)) => Ok((source, destination, packet.to_vec())),
+                Err(e) => Err(IoError::new(ErrorKind::InvalidData, e)),
+            });
+        }))
+    }
+
+    /// Measure write throughput of the device, writing `frame` repeatedly for `duration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame in framing of the device (with packet info and virtio header, if enabled).
+    /// * `duration` - Duration of the measurement.
+    ///
+    /// # Remarks
+    ///
+    /// In non-blocking mode (see [Device::set_nonblocking]), writes failing with `WouldBlock` are counted as
+    /// backpressure ([BenchResult::would_block]) and retried immediately. Interrupted writes are retried too.
+    /// Device should be up, otherwise kernel refuses the writes.
+    ///
+    /// # Errors
+    ///
+    /// Any other write error stops the measurement and is returned.
+    pub fn bench_write(&mut self, frame: &[u8], duration: Duration) -> IoResult<BenchResult> {
+        let mut result = BenchResult::default();
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            match self.write(frame) {
+                Ok(written) => {
+                    result.frames += 1;
+                    result.bytes += written as u64;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => result.would_block += 1,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        result.elapsed = start.elapsed();
+        Ok(result)
+    }
+
+    /// Measure read throughput of the device, reading frames sent through it for `duration`.
+    ///
+    /// # Remarks
+    ///
+    /// Frames are read by [Device::read_deadline], so measurement ends on time in both blocking and
+    /// non-blocking mode, even if no frames are sent, and [BenchResult::would_block] is always zero.
+    ///
+    /// # Errors
+    ///
+    /// Any read error stops the measurement and is returned.
+    pub fn bench_read(&mut self, duration: Duration) -> IoResult<BenchResult> {
+        let mut result = BenchResult::default();
+        let mut buf = vec![0u8; MAX_FRAME_SIZE + self.prefix_len()];
+        let start = Instant::now();
+        let deadline = start + duration;
+        // Frames available after the deadline would be still read without waiting
+        while Instant::now() < deadline {
+            match self.read_deadline(&mut buf, deadline)? {
+                Some(read) => {
+                    result.frames += 1;
+                    result.bytes += read as u64;
+                }
+                None => break,
+            }
+        }
+        result.elapsed = start.elapsed();
+        Ok(result)
+    }
+
+    /// Set offloads, which userspace is able to handle (`TUNSETOFFLOAD`). Kernel then passes frames with
+    /// unfinished checksums or superframes instead of doing the work itself, which is described by
+    /// virtio header of each frame.
+    ///
+    /// # Errors
+    ///
+    /// If device was not opened with [DeviceBuilder::vnet_hdr], or offloads depend on missing `CSUM`,
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` is returned.
+    pub fn set_offload(&self, offload: Offload) -> Result<(), CreationError> {
+        if !self.vnet_hdr && !offload.is_empty() {
+            return Err(nix::Error::Sys(Errno::EINVAL).into());
+        }
+        tun_set_offload(&self.file, offload.bits())
+    }
+
+    /// Set whether TCP segments sent through the device may be passed to the userspace coalesced into
+    /// superframes (like GRO), or only as normal frames no larger than MTU.
+    ///
+    /// # Remarks
+    ///
+    /// Superframes are produced by TCP segmentation offload of the device, which is enabled by
+    /// `TSO4`/`TSO6` offloads. Enabling sets offloads to `CSUM | TSO4 | TSO6 | TSO_ECN`, disabling sets
+    /// them to `CSUM` only, so kernel segments TCP superframes before they are queued for reading, while
+    /// transport checksums may still be left unfinished (see [VirtioNetHeader::F_NEEDS_CSUM]). There is no
+    /// way to read current offloads from the device, so offloads set by [Device::set_offload] (e.g. `UFO`)
+    /// are replaced. Frames already queued for reading are not changed.
+    ///
+    /// # Errors
+    ///
+    /// If device was not opened with [DeviceBuilder::vnet_hdr],
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` is returned.
+    pub fn set_gro(&self, on: bool) -> Result<(), CreationError> {
+        let segmentation = if on { Offload::TSO4 | Offload::TSO6 | Offload::TSO_ECN } else { Offload::empty() };
+        self.set_offload(Offload::CSUM | segmentation)
+    }
+
+    /// Attach classic BPF socket filter (`TUNATTACHFILTER`), so only frames accepted by `program`
+    /// are passed to the userspace, others are dropped by kernel. Previously attached filter is replaced.
+    ///
+    /// # Remarks
+    ///
+    /// Filter is executed on frames starting with ethernet header, before packet info and virtio header
+    /// are prepended. Frame is truncated to the length returned by the filter, 0 drops it.
+    /// Filter belongs to the device, so it is shared by all its file descriptors and kept by persistent
+    /// device until [Device::detach_filter] is called.
+    ///
+    /// # Errors
+    ///
+    /// Only tap devices support socket filters, for tun device, or if kernel refuses the program
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` is returned.
+    pub fn attach_filter(&mut self, program: &[libc::sock_filter]) -> Result<(), CreationError> {
+        tun_attach_filter(&self.file, program)
+    }
+
+    /// Compile tcpdump-style expression (e.g. `"ip and tcp port 80"`) and attach it as socket filter,
+    /// see [compile_pcap_filter] for supported grammar and [Device::attach_filter] for filter semantics.
+    ///
+    /// # Errors
+    ///
+    /// Compiled filter works with ethernet frames, so for tun device
+    /// [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` (`InvalidInput`) is returned,
+    /// before the expression is compiled.
+    /// If expression is not supported, [CreationError::InvalidFilter](crate::error::CreationError) is returned.
+    #[cfg(feature = "pcap-filter")]
+    pub fn attach_pcap_filter(&mut self, expr: &str) -> Result<(), CreationError> {
+        if self.mode != DeviceMode::Tap {
+            return Err(nix::Error::Sys(Errno::EINVAL).into());
+        }
+        let program = compile_pcap_filter(expr)?;
+        self.attach_filter(&program)
+    }
+
+    /// Detach socket filter attached by [Device::attach_filter] (`TUNDETACHFILTER`), so all frames are
+    /// passed to the userspace again.
+    ///
+    /// # Errors
+    ///
+    /// For tun device, [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` is returned.
+    pub fn detach_filter(&mut self) -> Result<(), CreationError> {
+        tun_detach_filter(&self.file)
+    }
+
+    /// Attach eBPF socket filter (`TUNSETFILTEREBPF`), so frames are dropped or truncated by kernel
+    /// before they are queued for reading, saving read calls for unwanted frames. Previously attached
+    /// eBPF filter is replaced, `-1` detaches it.
+    ///
+    /// # Arguments
+    ///
+    /// * `prog_fd` - File descriptor of loaded `BPF_PROG_TYPE_SOCKET_FILTER` program, or `-1`. Kernel takes
+    ///   its own reference to the program, so descriptor can be closed after the call.
+    ///
+    /// # Remarks
+    ///
+    /// Requires Linux 4.16 or newer, loading the program itself requires `CAP_BPF` (or `CAP_SYS_ADMIN`).
+    /// Unlike [Device::attach_filter], it works with both tun and tap devices and is independent of
+    /// classic filter, frame has to be accepted by both. Filter belongs to the device, so it is shared by all
+    /// its file descriptors and kept by persistent device until it is detached.
+    ///
+    /// # Errors
+    ///
+    /// If `prog_fd` is not socket filter program, [CreationError::IoctlError](crate::error::CreationError)
+    /// with `EINVAL` (or `EBADF` for invalid descriptor) is returned.
+    pub fn attach_ebpf_filter(&self, prog_fd: RawFd) -> Result<(), CreationError> {
+        tun_set_filter_ebpf(&self.file, prog_fd)
+    }
+
+    /// Read single frame and split TCP superframe into segments fitting into `mtu`, see [segment_tcp].
+    ///
+    /// # Returns
+    ///
+    /// Segments (or just the frame, if it is not superframe), without packet info and virtio header,
+    /// with checksums finished.
+    ///
+    /// # Errors
+    ///
+    /// If device was not opened with [DeviceBuilder::vnet_hdr], `InvalidInput` error is returned.
+    /// If frame is UDP superframe, or it cannot be segmented, `InvalidData` error is returned.
+    pub fn read_and_segment(&mut self, mtu: usize) -> IoResult<Vec<Vec<u8>>> {
+        if !self.vnet_hdr {
+            return Err(IoError::new(ErrorKind::InvalidInput, "device does not have virtio header enabled"));
+        }
+        let prefix = self.prefix_len();
+        let mut frame = vec![0u8; MAX_FRAME_SIZE + prefix];
+        let read = self.read(&mut frame)?;
+        let header = frame.get(self.packet_info_len()..read).filter(|_| read >= prefix).and_then(VirtioNetHeader::parse)
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidData, "frame is shorter than virtio header"))?;
+        frame.truncate(read);
+        let mut packet = frame.split_off(prefix);
+
+        match header.gso_type & !VirtioNetHeader::GSO_ECN {
+            VirtioNetHeader::GSO_NONE => {
+                if header.flags & VirtioNetHeader::F_NEEDS_CSUM != 0 {
+                    finish_checksum(&mut packet, &header)?;
+                }
+                Ok(vec![packet])
+            }
+            VirtioNetHeader::GSO_TCPV4 | VirtioNetHeader::GSO_TCPV6 => segment_tcp(&packet, self.mode, mtu),
+            _ => Err(IoError::new(ErrorKind::InvalidData, "only TCP superframes can be segmented")),
+        }
+    }
+
+    /// Read frames until one, for which `keep` returns true, is found. Rest of the frames is discarded.
+    ///
+    /// # Returns
+    ///
+    /// Length of the kept frame, which is stored in the `buf`.
+    ///
+    /// # Remarks
+    ///
+    /// This is only software filter, every frame is still copied to the userspace.
+    /// If device is in non-blocking mode and no matching frame is available, `WouldBlock` error is returned.
+    pub fn read_filtered<F: Fn(&[u8]) -> bool>(&mut self, buf: &mut [u8], keep: F) -> IoResult<usize> {
+        loop {
+            let read = self.read(buf)?;
+            if keep(&buf[..read]) {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// Read single frame into `buf`, waiting for it at most until `deadline`.
+    ///
+    /// # Returns
+    ///
+    /// Length of the frame, or `None` if no frame arrived before the deadline.
+    ///
+    /// # Remarks
+    ///
+    /// Device is polled for the time remaining to the deadline, recomputed after every wakeup, so several
+    /// reads can share single deadline. If deadline has already passed, device is polled without waiting,
+    /// so frame, which is already available, is still returned. Works in both blocking and non-blocking mode.
+    pub fn read_deadline(&mut self, buf: &mut [u8], deadline: Instant) -> IoResult<Option<usize>> {
+        self.check_shutdown(Shutdown::Read)?;
+        loop {
+            // Frame buffered by peek is available without waiting
+            if self.stash.is_some() {
+                return self.read(buf).map(Some);
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // Round up, so poll does not return just before the deadline
+            let timeout = remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as libc::c_int;
+            let mut fd = libc::pollfd { fd: self.file.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            match unsafe { libc::poll(&mut fd, 1, timeout) } {
+                -1 => {
+                    let e = IoError::last_os_error();
+                    if e.kind() == ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(e);
+                }
+                0 if timeout == 0 || Instant::now() >= deadline => return Ok(None),
+                0 => continue,
+                _ => (),
+            }
+            match self.read(buf) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => (),
+                result => return result.map(Some),
+            }
+            if timeout == 0 {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Read single frame into stack allocated array of size `N`.
+    ///
+    /// # Returns
+    ///
+    /// Array containing the frame and length of the frame.
+    ///
+    /// # Errors
+    ///
+    /// Kernel silently truncates frames, which do not fit into the read buffer. To detect this, frame is
+    /// read into the array followed by one spare byte. If frame is larger than `N`, it is consumed
+    /// (and lost) and `InvalidData` error is returned.
+    pub fn read_array<const N: usize>(&mut self) -> IoResult<([u8; N], usize)> {
+        let mut frame = [0u8; N];
+        let mut overflow = [0u8; 1];
+        let read = self.read_frame(&mut [IoSliceMut::new(&mut frame), IoSliceMut::new(&mut overflow)])?;
+        if read > N {
+            return Err(IoError::new(ErrorKind::InvalidData, format!("frame does not fit into {} bytes", N)));
+        }
+        Ok((frame, read))
+    }
+
+    /// Read single frame into `buf` and write it also to the `mirror` device, e.g. to emulate
+    /// mirror (SPAN) port.
+    ///
+    /// # Returns
+    ///
+    /// Length of the frame stored in the `buf`, in framing of this device.
+    ///
+    /// # Remarks
+    ///
+    /// If only one of the devices has packet info enabled, [PacketInfo] is stripped from the frame,
+    /// or created by [PacketInfo::for_frame], before it is written to the `mirror`. Likewise virtio header
+    /// is stripped, or zeroed one (without offloads) is inserted. If both devices have virtio header, it is
+    /// kept, even if its size differs.
+    ///
+    /// # Errors
+    ///
+    /// If devices are not in the same mode, `InvalidInput` error is returned before reading.
+    /// If frame is shorter than its prefix (see [Device::prefix_len]), or it needs checksum or segmentation
+    /// offload, but `mirror` does not have virtio header enabled, `InvalidData` error is returned and frame
+    /// is not mirrored.
+    pub fn duplicate_to(&mut self, mirror: &mut Device, buf: &mut [u8]) -> IoResult<usize> {
+        if self.mode != mirror.mode {
+            return Err(IoError::new(ErrorKind::InvalidInput, "frames cannot be mirrored between tun and tap device"));
+        }
+        mirror.check_shutdown(Shutdown::Write)?;
+        let read = self.read(buf)?;
+        self.mirror_frame(mirror, &buf[..read])?;
+        Ok(read)
+    }
+
+    /// Create reader, which reads frames from this device and writes copy of each frame also to the `mirror`
+    /// device, see [TeeReader].
+    pub fn tee<'a>(&'a mut self, mirror: &'a mut Device) -> TeeReader<'a> {
+        TeeReader { device: self, mirror, mirror_errors: 0 }
+    }
+
+    /// Write `frame` read from this device to the `mirror`, converting packet info and virtio header if needed.
+    fn mirror_frame(&self, mirror: &mut Device, frame: &[u8]) -> IoResult<()> {
+        let (framed, expected) = if (self.packet_info, self.vnet_hdr_len) == (mirror.packet_info, mirror.vnet_hdr_len) {
+            (mirror.write(frame)?, frame.len())
+        } else {
+            let framed = self.reframe(mirror, frame)?;
+            (mirror.write(&framed)?, framed.len())
+        };
+        if framed != expected {
+            return Err(IoError::new(ErrorKind::WriteZero, "frame was not mirrored completely"));
+        }
+        Ok(())
+    }
+
+    /// Convert prefix of `frame` read from this device to the prefix used by `target`, see [Device::duplicate_to].
+    fn reframe(&self, target: &Device, frame: &[u8]) -> IoResult<Vec<u8>> {
+        let packet = frame.get(self.prefix_len()..)
+            .ok_or_else(|| IoError::new(ErrorKind::InvalidData, "frame is shorter than its prefix"))?;
+        let mut framed = Vec::with_capacity(target.prefix_len() + packet.len());
+        if target.packet_info && self.packet_info {
+            framed.extend_from_slice(&frame[..PacketInfo::SIZE]);
+        } else if target.packet_info {
+            framed.extend_from_slice(&PacketInfo::for_frame(packet, self.mode).to_bytes());
+        }
+        let header = &frame[self.packet_info_len()..self.prefix_len()];
+        if target.vnet_hdr {
+            framed.extend_from_slice(&header[..header.len().min(target.vnet_hdr_len)]);
+            framed.resize(target.prefix_len(), 0);
+        } else if let Some(header) = VirtioNetHeader::parse(header) {
+            if header.flags & VirtioNetHeader::F_NEEDS_CSUM != 0 || header.gso_type != VirtioNetHeader::GSO_NONE {
+                return Err(IoError::new(ErrorKind::InvalidData, "frame with offloads requires virtio header"));
+            }
+        }
+        framed.extend_from_slice(packet);
+        Ok(framed)
+    }
+
+    /// Write frames captured in pcap file to the device, after they are changed by `rewrite`, e.g. to replay
+    /// captured traffic in network with different addresses (see [rewrite_mac](crate::unix::rewrite_mac)
+    /// and [rewrite_ipv4](crate::unix::rewrite_ipv4)).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of classic pcap file (not pcapng) with ethernet frames for tap or raw IP packets for tun.
+    /// * `rewrite` - Called with each captured frame (without packet info and virtio header) before it is written.
+    ///
+    /// # Returns
+    ///
+    /// Number of written frames.
+    ///
+    /// # Remarks
+    ///
+    /// Frames are written as fast as possible, timestamps of the capture are ignored. Packet info (created by
+    /// [PacketInfo::for_frame]) and empty virtio header are prepended to frames, if the device has them enabled.
+    /// Frames truncated by capture are written as they were captured.
+    ///
+    /// # Errors
+    ///
+    /// If file is not pcap file or its frames do not match mode of the device, `InvalidData` error is returned
+    /// before writing. Error of reading the file or writing the device stops the replay.
+    pub fn replay_pcap_rewrite<P, F>(&mut self, path: P, mut rewrite: F) -> IoResult<usize>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&mut [u8]),
+    {
+        self.check_shutdown(Shutdown::Write)?;
+        let mut pcap = PcapReader::new(BufReader::new(File::open(path)?))?;
+        if !pcap.matches(self.mode) {
+            let message = format!("captured frames cannot be written to {} device", self.mode);
+            return Err(IoError::new(ErrorKind::InvalidData, message));
+        }
+        let mut count = 0;
+        while let Some(mut frame) = pcap.next_frame()? {
+            rewrite(&mut frame);
+            let mut prefix = vec![0u8; self.prefix_len()];
+            if self.packet_info {
+                prefix[..PacketInfo::SIZE].copy_from_slice(&PacketInfo::for_frame(&frame, self.mode).to_bytes());
+            }
+            let written = self.file.write_vectored(&[IoSlice::new(&prefix), IoSlice::new(&frame)])?;
+            self.counters.count_written(written);
+            if written != prefix.len() + frame.len() {
+                return Err(IoError::new(ErrorKind::WriteZero, "frame was not written completely"));
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Set if tap device should pass to the userspace only unicast frames addressed to its MAC address.
+    ///
+    /// # Remarks
+    ///
+    /// Interface flags are not affected, `IFF_BROADCAST` is volatile flag, which cannot be cleared from
+    /// userspace, and clearing `IFF_MULTICAST` only stops the kernel from sending multicast, not from
+    /// receiving it. Instead tun frame filter (`TUNSETTXFILTER`) is set to the current MAC address of the
+    /// device, which drops broadcast and multicast frames before they are queued for reading.
+    /// If MAC address of the device changes, filter must be set again. Promiscuous mode does not bypass the filter.
+    ///
+    /// # Errors
+    ///
+    /// Filter works with ethernet frames, so for tun device [CreationError::IoctlError](crate::error::CreationError)
+    /// with `EINVAL` is returned.
+    pub fn set_unicast_only(&self, on: bool) -> Result<(), CreationError> {
+        let filter = if on {
+            TxFilter::address(get_hw_address(&get_control_socket()?, &self.name)?)
+        } else {
+            TxFilter::disabled()
+        };
+        tun_set_tx_filter(&self.file, &filter)
+    }
+
+    /// Enable or disable promiscuous mode, using reference counted membership.
+    ///
+    /// # Remarks
+    ///
+    /// Kernel counts promiscuous mode requests of all users (bridges, packet sockets, ...), but request
+    /// made by changing `IFF_PROMISC` flag counts only once, so two independent users toggling the flag
+    /// clobber each other. Instead membership (`PACKET_MR_PROMISC`) is held by packet socket owned by
+    /// this device, every enable must be matched with disable, and all memberships are released when
+    /// device is dropped.
+    pub fn set_promiscuous_refcounted(&mut self, on: bool) -> Result<(), CreationError> {
+        self.set_membership(libc::PACKET_MR_PROMISC, on)
+    }
+
+    /// Enable or disable all-multicast mode, using reference counted membership.
+    /// See [Device::set_promiscuous_refcounted] for details.
+    pub fn set_allmulti_refcounted(&mut self, on: bool) -> Result<(), CreationError> {
+        self.set_membership(libc::PACKET_MR_ALLMULTI, on)
+    }
+
+    /// Enable promiscuous mode (`IFF_PROMISC`) until returned guard is dropped, e.g. for duration of capture.
+    ///
+    /// # Remarks
+    ///
+    /// Previous state of the flag is recorded, so device which was already promiscuous stays promiscuous after
+    /// the guard is dropped. Guard does not borrow the device and restores the flag also when capture loop
+    /// panics, but not when process is killed or aborts, see [Device::set_promiscuous_refcounted] for mode
+    /// released by kernel in that case. Guard restores the flag by name, so device must not be renamed
+    /// meanwhile. Error of restoring the flag is ignored (logged with `log` feature enabled).
+    pub fn promiscuous_scope(&self) -> Result<PromiscGuard, CreationError> {
+        let socket = get_control_socket()?;
+        let flags = get_flags(&socket, &self.name)?;
+        let was_promiscuous = flags & libc::IFF_PROMISC as libc::c_short != 0;
+        if !was_promiscuous {
+            set_flags(&socket, &self.name, flags | libc::IFF_PROMISC as libc::c_short)?;
+        }
+        Ok(PromiscGuard { name: self.name.clone(), was_promiscuous })
+    }
+
+    fn set_membership(&mut self, kind: libc::c_int, add: bool) -> Result<(), CreationError> {
+        if self.membership.is_none() {
+            self.membership = Some(get_membership_socket()?);
+        }
+        let index = interface_index(&self.name)?;
+        set_membership(self.membership.as_ref().unwrap(), index, kind, add)
+    }
+
+    /// Apply complete receive filtering state of the device.
+    ///
+    /// # Remarks
+    ///
+    /// `IFF_PROMISC` and `IFF_ALLMULTI` flags are set or cleared, and multicast groups joined by
+    /// userspace are diffed against `mode.groups`, joining missing (`SIOCADDMULTI`) and leaving
+    /// extra (`SIOCDELMULTI`) ones. Groups joined by kernel are kept. Flags are independent of
+    /// [Device::set_promiscuous_refcounted] and [Device::set_allmulti_refcounted] memberships.
+    ///
+    /// # Errors
+    ///
+    /// If any ioctl call fail, [CreationError::IoctlError](crate::error::CreationError) is returned,
+    /// and state may be applied only partially.
+    pub fn set_rx_mode(&mut self, mode: RxMode) -> Result<(), CreationError> {
+        let mut set = 0;
+        if mode.promisc {
+            set |= libc::IFF_PROMISC;
+        }
+        if mode.allmulti {
+            set |= libc::IFF_ALLMULTI;
+        }
+        self.update_flags(libc::IFF_PROMISC | libc::IFF_ALLMULTI, set)?;
+
+        let socket = get_control_socket()?;
+        let current = multicast_groups(&self.name)?;
+        for group in current.iter().filter(|group| !mode.groups.contains(group)) {
+            set_multicast(&socket, &self.name, *group, false)?;
+        }
+        for group in mode.groups.iter().filter(|group| !current.contains(group)) {
+            set_multicast(&socket, &self.name, *group, true)?;
+        }
+        Ok(())
+    }
+
+    /// Read current configuration of the device, from which it can be recreated with [DeviceConfig::open].
+    ///
+    /// # Remarks
+    ///
+    /// Persistence is read with `TUNGETIFF`, but packet info is taken from the device, as kernel reports
+    /// `IFF_NOFILTER`, which has the same value as `IFF_NO_PI`. Interface fields, which are not set
+    /// (IPv4 address, netmask and broadcast), are `None`, as are additional flags. MAC address is read
+    /// only for tap devices.
+    ///
+    /// # Errors
+    ///
+    /// If any ioctl call fail, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    pub fn to_config(&self) -> Result<DeviceConfig, CreationError> {
+        let flags = tun_get_interface(&self.file)?.get_fru().get_flags() as libc::c_int;
+        let socket = get_control_socket()?;
+        let interface = InterfaceConfig {
+            address: get_address(&socket, &self.name)?,
+            netmask: get_netmask(&socket, &self.name)?,
+            broadcast: get_broadcast_address(&socket, &self.name)?.filter(|address| !address.is_unspecified()),
+            mtu: Some(get_mtu(&socket, &self.name)? as u32),
+            mac: match self.mode {
+                DeviceMode::Tap => Some(get_hw_address(&socket, &self.name)?),
+                DeviceMode::Tun => None,
+            },
+            up: Some(get_flags(&socket, &self.name)? as libc::c_int & libc::IFF_UP != 0),
+            flags: None,
+        };
+        Ok(DeviceConfig {
+            name: self.name.clone(),
+            mode: self.mode,
+            packet_info: self.packet_info,
+            vnet_hdr: self.vnet_hdr,
+            persistent: flags & libc::IFF_PERSIST != 0,
+            interface,
+        })
+    }
+
+    /// Apply all fields of `config`, which are not `None`.
+    ///
+    /// # Remarks
+    ///
+    /// Fields are applied in order: MAC address, MTU, flags, address, netmask, broadcast address and
+    /// finally up/down state. Setting an address resets netmask to the default of the address class, so
+    /// netmask is set after the address, and broadcast address after both. Device is brought up last,
+    /// when it is fully configured. Already applied steps are not rolled back on error.
+    ///
+    /// # Errors
+    ///
+    /// If any step fails, [CreationError::ConfigFailed](crate::error::CreationError) is returned,
+    /// containing name of the failed step and its error.
+    pub fn apply(&self, config: &InterfaceConfig) -> Result<(), CreationError> {
+        fn step<T>(step: &'static str, result: Result<T, CreationError>) -> Result<T, CreationError> {
+            result.map_err(|e| CreationError::ConfigFailed { step, source: Box::new(e) })
+        }
+
+        let socket = step("control socket", get_control_socket())?;
+        if let Some(mac) = config.mac {
+            let request = InterfaceRequest::new(&self.name, InterfaceFieldReplaceUnit::hw_address(mac));
+            let request = step("MAC address", request.map_err(Into::into))?;
+            step("MAC address", set_hw_address(&socket, &request))?;
+        }
+        if let Some(mtu) = config.mtu {
+            step("MTU", set_mtu(&socket, &self.name, mtu as libc::c_int))?;
+        }
+        if let Some(flags) = config.flags {
+            step("flags", self.update_flags(flags, flags))?;
+        }
+        if let Some(address) = config.address {
+            step("address", set_address(&socket, &self.name, address))?;
+        }
+        if let Some(netmask) = config.netmask {
+            step("netmask", set_netmask(&socket, &self.name, netmask))?;
+        }
+        if let Some(broadcast) = config.broadcast {
+            step("broadcast address", set_broadcast_address(&socket, &self.name, broadcast))?;
+        }
+        if let Some(up) = config.up {
+            step("up/down state", self.update_flags(libc::IFF_UP, if up { libc::IFF_UP } else { 0 }))?;
+        }
+        Ok(())
+    }
+
+    /// Assign IPv4 address with netmask given by `prefix` length, and optionally bring the device up.
+    ///
+    /// # Remarks
+    ///
+    /// Connected route of the subnet is installed by the kernel, once the device is up and has the address.
+    /// If any step fails, previous address and netmask of the device are restored (address is removed,
+    /// if device had none). Device is never brought down.
+    ///
+    /// # Errors
+    ///
+    /// If prefix is longer than 32, [CreationError::IoctlError](crate::error::CreationError) with `EINVAL` is returned.
+    /// If any step fails, [CreationError::ConfigFailed](crate::error::CreationError) is returned, see [Device::apply].
+    pub fn setup_ipv4(&mut self, addr: Ipv4Addr, prefix: u8, bring_up: bool) -> Result<(), CreationError> {
+        if prefix > 32 {
+            return Err(nix::Error::Sys(Errno::EINVAL).into());
+        }
+        let netmask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+        let config = InterfaceConfig {
+            address: Some(addr),
+            netmask: Some(Ipv4Addr::from(netmask)),
+            up: if bring_up { Some(true) } else { None },
+            ..Default::default()
+        };
+        let socket = get_control_socket()?;
+        let previous = get_address(&socket, &self.name)?;
+        let previous_netmask = get_netmask(&socket, &self.name)?;
+        let result = self.apply(&config);
+        if result.is_err() {
+            // Rollback is best effort, error of the failed step is reported. Setting address resets
+            // netmask, so netmask is restored after it.
+            let _ = set_address(&socket, &self.name, previous.unwrap_or(Ipv4Addr::UNSPECIFIED));
+            if let (Some(_), Some(netmask)) = (previous, previous_netmask) {
+                let _ = set_netmask(&socket, &self.name, netmask);
+            }
+        }
+        result
+    }
+
+    /// Returns IPv4 address of the device with prefix length of its netmask, e.g. `10.0.0.1/24`,
+    /// or `None` if device does not have IPv4 address.
+    ///
+    /// # Errors
+    ///
+    /// If netmask is not contiguous (e.g. `255.0.255.0`), [CreationError::IoctlError](crate::error::CreationError)
+    /// with `EINVAL` is returned.
+    pub fn cidr(&self) -> Result<Option<String>, CreationError> {
+        let socket = get_control_socket()?;
+        let address = match get_address(&socket, &self.name)?.filter(|address| !address.is_unspecified()) {
+            Some(address) => address,
+            None => return Ok(None),
+        };
+        let netmask = get_netmask(&socket, &self.name)?.unwrap_or(Ipv4Addr::UNSPECIFIED);
+        let prefix = netmask_prefix(netmask).ok_or(nix::Error::Sys(Errno::EINVAL))?;
+        Ok(Some(format!("{}/{}", address, prefix)))
+    }
+
+    /// Set IPv4 broadcast address derived from address and netmask of the device (`address | !netmask`),
+    /// like `ifconfig` does when broadcast address is not given.
+    ///
+    /// # Errors
+    ///
+    /// If device does not have IPv4 address (and netmask), [CreationError::IoctlError](crate::error::CreationError)
+    /// with `EADDRNOTAVAIL` is returned.
+    pub fn set_broadcast_auto(&self) -> Result<(), CreationError> {
+        let socket = get_control_socket()?;
+        let address = get_address(&socket, &self.name)?.filter(|address| !address.is_unspecified());
+        let netmask = get_netmask(&socket, &self.name)?;
+        let (address, netmask) = match (address, netmask) {
+            (Some(address), Some(netmask)) => (address, netmask),
+            _ => return Err(nix::Error::Sys(Errno::EADDRNOTAVAIL).into()),
+        };
+        set_broadcast_address(&socket, &self.name, broadcast_address(address, netmask))
+    }
+
+    /// Returns true, if device is point-to-point interface (`IFF_POINTOPOINT`).
+    ///
+    /// # Remarks
+    ///
+    /// Destination (peer) address of the device (`SIOCSIFDSTADDR`) is meaningful only for
+    /// point-to-point interface, on other interfaces it is ignored.
+    ///
+    /// Kernel sets the flag for tun devices and clears it for tap devices when they are created. The flag
+    /// cannot be changed later, as kernel silently ignores its changes made by `SIOCSIFFLAGS`.
+    pub fn is_pointopoint(&self) -> Result<bool, CreationError> {
+        let flags = get_flags(&get_control_socket()?, &self.name)? as libc::c_int;
+        Ok(flags & libc::IFF_POINTOPOINT != 0)
+    }
+
+    /// Read flags of the device, replace bits in `mask` with bits from `flags` and write them back,
+    /// if they changed.
+    fn update_flags(&self, mask: libc::c_int, flags: libc::c_int) -> Result<(), CreationError> {
+        let socket = get_control_socket()?;
+        let current = get_flags(&socket, &self.name)?;
+        let new = (current & !(mask as libc::c_short)) | (flags & mask) as libc::c_short;
+        if new != current {
+            set_flags(&socket, &self.name, new)?;
+        }
+        Ok(())
+    }
+
+    /// Wait until frame is available or link state of the device changes, at most `timeout`.
+    ///
+    /// # Returns
+    ///
+    /// Events, which occurred, or empty vector if timeout elapsed (or wait was interrupted by signal).
+    ///
+    /// # Remarks
+    ///
+    /// Device file descriptor is polled together with route netlink socket subscribed to link
+    /// notifications (`RTMGRP_LINK`), which is opened by the first call and kept by the device, so
+    /// changes made before the first call are not reported. On rename, [Device::name] is updated.
+    /// Device is writable unless its send buffer (see [Device::set_sndbuf]) is full, so writability
+    /// is not reported.
+    #[cfg(feature = "netlink")]
+    pub fn poll_events(&mut self, timeout: Duration) -> Result<Vec<DeviceEvent>, CreationError> {
+        if self.events.is_none() {
+            let socket = netlink::NetlinkSocket::subscribe(libc::RTMGRP_LINK as u32)?;
+            self.events = Some((socket, interface_index(&self.name)?));
+        }
+        let mut events = Vec::new();
+        let mut fds = [
+            libc::pollfd { fd: self.file.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+            libc::pollfd { fd: self.events.as_ref().unwrap().0.as_raw_fd(), events: libc::POLLIN, revents: 0 },
+        ];
+        // Frame buffered by peek is available without waiting
+        let timeout = if self.stash.is_some() { 0 } else { timeout.as_millis().min(i32::MAX as u128) as libc::c_int };
+        match Errno::result(unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) }) {
+            Err(nix::Error::Sys(Errno::EINTR)) => return Ok(events),
+            result => result?,
+        };
+        if self.stash.is_some() || fds[0].revents & libc::POLLIN != 0 {
+            events.push(DeviceEvent::Readable);
+        }
+        if fds[1].revents & libc::POLLIN != 0 {
+            let (socket, index) = self.events.as_mut().unwrap();
+            let name = &mut self.name;
+            socket.notifications(|kind, payload| {
+                use netlink::InterfaceInfoMessage;
+                let info = match netlink::header::<InterfaceInfoMessage>(payload) {
+                    Some(info) if kind == libc::RTM_NEWLINK && info.index as u32 == *index => info,
+                    _ => return,
+                };
+                if info.change & libc::IFF_UP as libc::c_uint != 0 {
+                    let up = info.flags & libc::IFF_UP as libc::c_uint != 0;
+                    events.push(if up { DeviceEvent::LinkUp } else { DeviceEvent::LinkDown });
+                }
+                let new_name = netlink::attributes::<InterfaceInfoMessage>(payload)
+                    .find(|(kind, _)| *kind == libc::IFLA_IFNAME)
+                    .and_then(|(_, data)| std::ffi::CStr::from_bytes_until_nul(data).ok()?.to_str().ok().map(String::from));
+                if let Some(new_name) = new_name.filter(|new_name| new_name != name) {
+                    *name = new_name.clone();
+                    events.push(DeviceEvent::Renamed(new_name));
+                }
+            })?;
+        }
+        Ok(events)
+    }
+
+    /// Returns blocking iterator over changes of the device (brought up or down, renamed, removed),
+    /// see [LinkEvents], which can be converted into asynchronous stream with `async` feature.
+    ///
+    /// # Remarks
+    ///
+    /// Unlike [Device::poll_events], iterator is independent of the device, so it can be moved to
+    /// supervising thread, and [Device::name] is not updated on rename.
+    #[cfg(feature = "netlink")]
+    pub fn watch(&self) -> Result<LinkEvents, CreationError> {
+        LinkEvents::new(interface_index(&self.name)?, &self.name)
+    }
+
+    /// Set network namespace id of the link peer (`IFLA_LINK_NETNSID`), used to reference peer
+    /// living in another network namespace.
+    ///
+    /// # Remarks
+    ///
+    /// `nsid` must be already assigned to the peer namespace (`RTM_NEWNSID`, e.g. `ip netns set <ns> <nsid>`),
+    /// otherwise kernel refuses the request with `EINVAL`.
+    #[cfg(feature = "netlink")]
+    pub fn set_link_netnsid(&mut self, nsid: i32) -> Result<(), CreationError> {
+        netlink::change_link(libc::RTM_NEWLINK, interface_index(&self.name)?, |message| {
+            message.attribute(libc::IFLA_LINK_NETNSID, &nsid.to_ne_bytes());
+        })
+    }
+
+    /// Ensure the device has IPv6 link-local address (`fe80::/64`) and wait until its duplicate address
+    /// detection (DAD) finishes, at most `timeout`.
+    ///
+    /// # Returns
+    ///
+    /// Usable link-local address of the device.
+    ///
+    /// # Remarks
+    ///
+    /// Kernel assigns link-local address derived from MAC address (EUI-64), when tap device is brought up,
+    /// but tun device has no MAC address and gets one only if `addr_gen_mode` sysctl of the device is set
+    /// to random. If device has no link-local address, address derived from MAC address (tap) or random one
+    /// (tun) is added. Address is tentative until DAD finishes, which happens only while the device is up.
+    /// DAD is skipped and address is usable immediately, if `accept_dad` or `dad_transmits` sysctl of the
+    /// device (`/proc/sys/net/ipv6/conf/<name>/`) is 0. Devices, which are down, time out.
+    ///
+    /// # Errors
+    ///
+    /// If DAD found the address is used by other host, [CreationError::IoctlError](crate::error::CreationError)
+    /// with `EADDRINUSE` is returned, if it did not finish before timeout, `ETIMEDOUT` is returned. If IPv6 is
+    /// disabled on the device (`disable_ipv6` sysctl), kernel refuses the address with `EACCES`.
+    #[cfg(feature = "netlink")]
+    pub fn ensure_ipv6_link_local(&self, timeout: Duration) -> Result<Ipv6Addr, CreationError> {
+        use netlink::{AddressMessage, NetlinkMessage};
+
+        let index = interface_index(&self.name)?;
+        let mut socket = netlink::NetlinkSocket::open()?;
+        let deadline = Instant::now() + timeout;
+        let mut added = false;
+        loop {
+            let addresses = link_local_addresses(&mut socket, index)?;
+            let usable = addresses.iter()
+                .find(|(_, flags)| flags & (netlink::IFA_F_TENTATIVE | netlink::IFA_F_DADFAILED) == 0);
+            if let Some((address, _)) = usable {
+                return Ok(*address);
+            }
+            if !addresses.is_empty() && addresses.iter().all(|(_, flags)| flags & netlink::IFA_F_DADFAILED != 0) {
+                return Err(nix::Error::Sys(Errno::EADDRINUSE).into());
+            }
+            if addresses.is_empty() && !added {
+                let mut address = [0u8; 16];
+                address[..2].copy_from_slice(&[0xfe, 0x80]);
+                match self.mode {
+                    DeviceMode::Tap => {
+                        let mac = get_hw_address(&get_control_socket()?, &self.name)?;
+                        let identifier = [mac[0] ^ 0x02, mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]];
+                        address[8..].copy_from_slice(&identifier);
+                    }
+                    DeviceMode::Tun => random_bytes(&mut address[8..])?,
+                }
+                let header = AddressMessage {
+                    prefix_len: 64,
+                    scope: libc::RT_SCOPE_LINK,
+                    ..AddressMessage::new(libc::AF_INET6, index)
+                };
+                let flags = netlink::NLM_F_CREATE | netlink::NLM_F_EXCL;
+                let mut request = NetlinkMessage::new(libc::RTM_NEWADDR, flags, &header);
+                request.attribute(netlink::IFA_LOCAL, &address);
+                socket.request(request)?;
+                added = true;
+                continue;
+            }
+            if Instant::now() >= deadline {
+                return Err(nix::Error::Sys(Errno::ETIMEDOUT).into());
+            }
+            thread::sleep(DAD_POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+        }
+    }
+
+    /// Attach the device to existing bridge with index `bridge_ifindex`, like `ip link set <name> master <bridge>`.
+    ///
+    /// # Remarks
+    ///
+    /// Device is detached from its previous master (if any) first by kernel. Bridge port is created
+    /// with the device state, so frames are forwarded once both the device and the bridge are up.
+    ///
+    /// # Errors
+    ///
+    /// If there is no interface with given index, [CreationError::IoctlError](crate::error::CreationError)
+    /// with `ENODEV` is returned, if interface is not a bridge (has no `/sys/class/net/<name>/bridge`),
+    /// `EINVAL` is returned. If kernel refuses the request, its error code is returned.
+    #[cfg(feature = "netlink")]
+    pub fn set_bridge(&self, bridge_ifindex: u32) -> Result<(), CreationError> {
+        let bridge = interface_name(bridge_ifindex)
+            .ok_or(CreationError::IoctlError(nix::Error::Sys(Errno::ENODEV)))?;
+        if !std::path::Path::new(&format!("/sys/class/net/{}/bridge", bridge)).exists() {
+            return Err(nix::Error::Sys(Errno::EINVAL).into());
+        }
+        self.set_master(bridge_ifindex)
+    }
+
+    /// Detach the device from its bridge, like `ip link set <name> nomaster`. Does nothing, if device
+    /// is not attached to any bridge.
+    #[cfg(feature = "netlink")]
+    pub fn leave_bridge(&self) -> Result<(), CreationError> {
+        self.set_master(0)
+    }
+
+    /// Set master of the device (`IFLA_MASTER`), index 0 releases device from its master.
+    #[cfg(feature = "netlink")]
+    fn set_master(&self, master_index: u32) -> Result<(), CreationError> {
+        netlink::change_link(libc::RTM_SETLINK, interface_index(&self.name)?, |message| {
+            message.attribute(libc::IFLA_MASTER, &master_index.to_ne_bytes());
+        })
+    }
+
+    /// Remove all entries of the device from neighbour (ARP and NDP) tables, like `ip neigh flush dev <name>`.
+    ///
+    /// # Remarks
+    ///
+    /// Entries are dumped (`RTM_GETNEIGH`) and those belonging to the device are deleted one by one
+    /// (`RTM_DELNEIGH`), static (permanent) entries included. Entries removed in the meantime are ignored.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses any request, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    #[cfg(feature = "netlink")]
+    pub fn flush_neighbors(&self) -> Result<(), CreationError> {
+        use netlink::{NeighbourMessage, NetlinkMessage};
+
+        let index = interface_index(&self.name)?;
+        let mut socket = netlink::NetlinkSocket::open()?;
+        let mut entries = Vec::new();
+        let request = NetlinkMessage::new(libc::RTM_GETNEIGH, 0, &NeighbourMessage::new(libc::AF_UNSPEC, index));
+        socket.dump(request, |kind, payload| {
+            let header = match netlink::header::<NeighbourMessage>(payload) {
+                Some(header) if kind == libc::RTM_NEWNEIGH && header.index as u32 == index => header,
+                _ => return,
+            };
+            let destination = netlink::attributes::<NeighbourMessage>(payload)
+                .find(|(kind, _)| *kind == libc::NDA_DST)
+                .map(|(_, data)| data.to_vec());
+            if let Some(destination) = destination {
+                entries.push((header.family, destination));
+            }
+        })?;
+
+        for (family, destination) in entries {
+            let header = NeighbourMessage::new(family as libc::c_int, index);
+            let mut request = NetlinkMessage::new(libc::RTM_DELNEIGH, 0, &header);
+            request.attribute(libc::NDA_DST, &destination);
+            match socket.request(request) {
+                Err(CreationError::IoctlError(nix::Error::Sys(Errno::ENOENT))) => (),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Reset the device to the state of newly created device, e.g. to reuse it between test cases. Device
+    /// is brought down, all its addresses are removed, MTU is set to [DeviceMode::default_mtu] and
+    /// `IFF_PROMISC`, `IFF_ALLMULTI` and `IFF_NOARP` flags are cleared (except `IFF_NOARP` of tun device,
+    /// which is always created with it).
+    ///
+    /// # Remarks
+    ///
+    /// Flags and MTU are set by ioctls. With `netlink` feature, addresses are dumped (`RTM_GETADDR`) and deleted
+    /// one by one (`RTM_DELADDR`), both IPv4 and IPv6 (kernel removes most IPv6 addresses itself, when device is
+    /// brought down), addresses removed in the meantime are ignored. Without it, only IPv4 addresses labeled with
+    /// the name of the device are removed, by setting address `0.0.0.0` (`SIOCSIFADDR`), while IPv4 aliases and
+    /// IPv6 addresses are kept. Only flags set by `SIOCSIFFLAGS` are cleared, promiscuous mode and all-multicast
+    /// mode requested by other users (e.g. [Device::set_promiscuous_refcounted] or bridge) are kept.
+    /// Other settings (MAC address, queue length, bridge, filters) are not changed.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses any request, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    pub fn reset(&self) -> Result<(), CreationError> {
+        let socket = get_control_socket()?;
+        let cleared = libc::IFF_UP | libc::IFF_PROMISC | libc::IFF_ALLMULTI | libc::IFF_NOARP;
+        let mut flags = get_flags(&socket, &self.name)? & !cleared as libc::c_short;
+        if self.mode == DeviceMode::Tun {
+            flags |= libc::IFF_NOARP as libc::c_short;
+        }
+        set_flags(&socket, &self.name, flags)?;
+        set_mtu(&socket, &self.name, self.mode.default_mtu() as libc::c_int)?;
+        self.remove_addresses(&socket)
+    }
+
+    /// Remove all addresses of the device, see [Device::reset].
+    #[cfg(feature = "netlink")]
+    fn remove_addresses(&self, _socket: &File) -> Result<(), CreationError> {
+        use netlink::{AddressMessage, NetlinkMessage};
+
+        let index = interface_index(&self.name)?;
+        let mut socket = netlink::NetlinkSocket::open()?;
+        let mut addresses = Vec::new();
+        let request = NetlinkMessage::new(libc::RTM_GETADDR, 0, &AddressMessage::new(libc::AF_UNSPEC, index));
+        socket.dump(request, |kind, payload| {
+            let header = match netlink::header::<AddressMessage>(payload) {
+                Some(header) if kind == libc::RTM_NEWADDR && header.index == index => header,
+                _ => return,
+            };
+            // IPv6 addresses have only IFA_ADDRESS, IFA_ADDRESS of IPv4 point-to-point address is the peer
+            let (mut local, mut address) = (None, None);
+            for (kind, data) in netlink::attributes::<AddressMessage>(payload) {
+                match kind {
+                    netlink::IFA_LOCAL => local = Some(data.to_vec()),
+                    netlink::IFA_ADDRESS => address = Some(data.to_vec()),
+                    _ => (),
+                }
+            }
+            if let Some(local) = local.or(address) {
+                addresses.push((header, local));
+            }
+        })?;
+
+        for (header, local) in addresses {
+            let header = AddressMessage { flags: 0, ..header };
+            let mut request = NetlinkMessage::new(libc::RTM_DELADDR, 0, &header);
+            request.attribute(netlink::IFA_LOCAL, &local);
+            match socket.request(request) {
+                Err(CreationError::IoctlError(nix::Error::Sys(Errno::EADDRNOTAVAIL))) => (),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove IPv4 addresses of the device, which are visible to ioctls, see [Device::reset].
+    #[cfg(not(feature = "netlink"))]
+    fn remove_addresses(&self, socket: &File) -> Result<(), CreationError> {
+        // Address 0.0.0.0 deletes the first address labeled with the device name, the next one is read after it
+        while get_address(socket, &self.name)?.is_some() {
+            set_address(socket, &self.name, Ipv4Addr::UNSPECIFIED)?;
+        }
+        Ok(())
+    }
+
+    /// Add static entry (`NUD_NOARP`) to forwarding database of the bridge the device is attached to,
+    /// like `bridge fdb replace <mac> dev <name> master static [vlan <vlan>]`, so frames for `mac` are
+    /// forwarded to the device without learning. Existing entry of the same address and VLAN is replaced.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses the request, [CreationError::IoctlError](crate::error::CreationError) is returned,
+    /// `EOPNOTSUPP` if device is not attached to a bridge (see [Device::set_bridge]) and `EINVAL`
+    /// for multicast address or VLAN not configured on the port of VLAN filtering bridge.
+    #[cfg(feature = "netlink")]
+    pub fn fdb_add(&mut self, mac: [u8; 6], vlan: Option<u16>) -> Result<(), CreationError> {
+        use netlink::{NeighbourMessage, NetlinkMessage};
+
+        let mut header = NeighbourMessage::new(libc::AF_BRIDGE, interface_index(&self.name)?);
+        header.state = libc::NUD_NOARP;
+        header.flags = libc::NTF_MASTER;
+        let flags = netlink::NLM_F_CREATE | netlink::NLM_F_REPLACE;
+        let mut request = NetlinkMessage::new(libc::RTM_NEWNEIGH, flags, &header);
+        request.attribute(libc::NDA_LLADDR, &mac);
+        if let Some(vlan) = vlan {
+            request.attribute(libc::NDA_VLAN, &vlan.to_ne_bytes());
+        }
+        netlink::NetlinkSocket::open()?.request(request)
+    }
+
+    /// Returns forwarding database entries associated with the device, like `bridge fdb show dev <name>`,
+    /// both entries of its bridge (learned and static) and the device's own address list.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses the request, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    #[cfg(feature = "netlink")]
+    pub fn fdb_list(&self) -> Result<Vec<FdbEntry>, CreationError> {
+        use netlink::{NeighbourMessage, NetlinkMessage};
+        use std::convert::TryInto;
+
+        let index = interface_index(&self.name)?;
+        let mut entries = Vec::new();
+        let request = NetlinkMessage::new(libc::RTM_GETNEIGH, 0, &NeighbourMessage::new(libc::AF_BRIDGE, 0));
+        netlink::NetlinkSocket::open()?.dump(request, |kind, payload| {
+            let header = match netlink::header::<NeighbourMessage>(payload) {
+                Some(header) if kind == libc::RTM_NEWNEIGH && header.index as u32 == index => header,
+                _ => return,
+            };
+            let mut mac = None;
+            let mut vlan = None;
+            let mut master = false;
+            for (kind, data) in netlink::attributes::<NeighbourMessage>(payload) {
+                match kind {
+                    libc::NDA_LLADDR if data.len() == 6 => mac = data.try_into().ok(),
+                    libc::NDA_VLAN if data.len() == 2 => vlan = Some(u16::from_ne_bytes([data[0], data[1]])),
+                    libc::NDA_MASTER => master = true,
+                    _ => (),
+                }
+            }
+            if let Some(mac) = mac {
+                entries.push(FdbEntry {
+                    mac,
+                    vlan,
+                    master,
+                    permanent: header.state & (libc::NUD_NOARP | libc::NUD_PERMANENT) != 0,
+                });
+            }
+        })?;
+        Ok(entries)
+    }
+
+    /// Set MTU of the device (`SIOCSIFMTU`) and of its connected routes, like `ip link set <name> mtu <mtu>`
+    /// followed by `ip route change <subnet> dev <name> proto kernel scope link src <address> mtu <mtu>`.
+    ///
+    /// # Remarks
+    ///
+    /// MTU metric of IPv4 route takes precedence over MTU of its device, so route with explicit MTU larger
+    /// than the device MTU makes the stack send packets, which do not fit into the device. Therefore every
+    /// IPv4 connected route (unicast route of `kernel` protocol with `link` scope in the main table) leading
+    /// through the device gets MTU metric set to `mtu`, whether it had any before or not. Other routes
+    /// (e.g. gateway routes) are left unchanged. IPv6 routes are left unchanged too, as the kernel lowers
+    /// MTU of IPv6 routes of the device itself.
+    ///
+    /// # Errors
+    ///
+    /// If MTU of the device cannot be set, [CreationError::IoctlError](crate::error::CreationError) with
+    /// `EINVAL` is returned and routes are not changed. If some route cannot be changed,
+    /// [CreationError::IoctlError](crate::error::CreationError) is returned, while MTU of the device
+    /// (and routes changed before) stays changed.
+    #[cfg(feature = "netlink")]
+    pub fn set_mtu_with_routes(&self, mtu: u32) -> Result<(), CreationError> {
+        use netlink::{NetlinkMessage, RouteMessage};
+
+        set_mtu(&get_control_socket()?, &self.name, mtu as libc::c_int)?;
+
+        let index = interface_index(&self.name)?;
+        let mut socket = netlink::NetlinkSocket::open()?;
+        let mut routes = Vec::new();
+        let request = NetlinkMessage::new(libc::RTM_GETROUTE, 0, &RouteMessage::new(libc::AF_INET));
+        socket.dump(request, |kind, payload| {
+            let header = match netlink::header::<RouteMessage>(payload) {
+                Some(header) if kind == libc::RTM_NEWROUTE => header,
+                _ => return,
+            };
+            if header.table != libc::RT_TABLE_MAIN
+                || header.protocol != libc::RTPROT_KERNEL
+                || header.scope != libc::RT_SCOPE_LINK
+                || header.kind != libc::RTN_UNICAST
+            {
+                return;
+            }
+            let kept = [libc::RTA_DST, libc::RTA_PREFSRC, libc::RTA_PRIORITY, libc::RTA_OIF];
+            let attributes: Vec<_> = netlink::attributes::<RouteMessage>(payload)
+                .filter(|(kind, _)| kept.contains(kind))
+                .map(|(kind, data)| (kind, data.to_vec()))
+                .collect();
+            if attributes.iter().any(|(kind, data)| *kind == libc::RTA_OIF && data[..] == index.to_ne_bytes()) {
+                routes.push((header, attributes));
+            }
+        })?;
+
+        for (header, attributes) in routes {
+            let mut request = NetlinkMessage::new(libc::RTM_NEWROUTE, netlink::NLM_F_REPLACE, &header);
+            for (kind, data) in &attributes {
+                request.attribute(*kind, data);
+            }
+            request.nested(libc::RTA_METRICS, |metrics| {
+                metrics.attribute(netlink::RTAX_MTU, &mtu.to_ne_bytes());
+            });
+            socket.request(request)?;
+        }
+        Ok(())
+    }
+
+    /// Mark every packet entering the system from the device (written to it) with firewall mark
+    /// `mark`, so they can be routed by fwmark policy rules (`ip rule add fwmark <mark> ...`).
+    ///
+    /// # Remarks
+    ///
+    /// There is no per-interface fwmark attribute, so traffic control filter is installed on the
+    /// device, like `tc qdisc add dev <name> clsact` followed by
+    /// `tc filter add dev <name> ingress prio 65520 u32 match u32 0 0 action skbedit mark <mark>`.
+    /// Mark is set before routing decision, so it is matched by policy rules, netfilter sees it too.
+    /// Existing `clsact` qdisc is reused, previous mark is replaced. Filter is removed with the device
+    /// or with [Device::clear_fwmark].
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses any request, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    /// Kernel without `clsact` qdisc, `u32` classifier or `skbedit` action reports `ENOENT` or `EOPNOTSUPP`.
+    #[cfg(feature = "netlink")]
+    pub fn set_fwmark(&mut self, mark: u32) -> Result<(), CreationError> {
+        use netlink::{NetlinkMessage, SkbEditParameters, TrafficControlMessage, U32Selector};
+
+        let index = interface_index(&self.name)?;
+        let mut socket = netlink::NetlinkSocket::open()?;
+        let flags = netlink::NLM_F_CREATE | netlink::NLM_F_EXCL;
+        let header = TrafficControlMessage::new(index, netlink::TC_H_CLSACT, 0xffff_0000, 0);
+        let mut request = NetlinkMessage::new(libc::RTM_NEWQDISC, flags, &header);
+        request.attribute(libc::TCA_KIND, b"clsact\0");
+        match socket.request(request) {
+            Err(CreationError::IoctlError(nix::Error::Sys(Errno::EEXIST))) => (),
+            result => result?,
+        }
+
+        self.clear_fwmark()?;
+        let selector = U32Selector {
+            flags: netlink::TC_U32_TERMINAL,
+            keys: 1,
+            ..Default::default()
+        };
+        let parameters = SkbEditParameters {
+            action: netlink::TC_ACT_PIPE,
+            ..Default::default()
+        };
+        let mut request = NetlinkMessage::new(libc::RTM_NEWTFILTER, flags, &Self::fwmark_filter(index));
+        request.attribute(libc::TCA_KIND, b"u32\0").nested(libc::TCA_OPTIONS, |options| {
+            options.attribute(netlink::TCA_U32_SEL, netlink::as_bytes(&selector));
+            options.nested(netlink::TCA_U32_ACT, |actions| {
+                actions.nested(1, |action| {
+                    action.attribute(netlink::TCA_ACT_KIND, b"skbedit\0");
+                    action.nested(netlink::TCA_ACT_OPTIONS, |options| {
+                        options.attribute(netlink::TCA_SKBEDIT_PARMS, netlink::as_bytes(&parameters))
+                            .attribute(netlink::TCA_SKBEDIT_MARK, &mark.to_ne_bytes());
+                    });
+                });
+            });
+        });
+        socket.request(request)
+    }
+
+    /// Remove filter installed by [Device::set_fwmark], so packets from the device are not marked
+    /// anymore. `clsact` qdisc is kept, as other filters may be attached to it. Does nothing, if mark is not set.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses the request, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    #[cfg(feature = "netlink")]
+    pub fn clear_fwmark(&mut self) -> Result<(), CreationError> {
+        let header = Self::fwmark_filter(interface_index(&self.name)?);
+        let request = netlink::NetlinkMessage::new(libc::RTM_DELTFILTER, 0, &header);
+        match netlink::NetlinkSocket::open()?.request(request) {
+            Err(CreationError::IoctlError(nix::Error::Sys(Errno::ENOENT)))
+            | Err(CreationError::IoctlError(nix::Error::Sys(Errno::EINVAL))) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Header addressing ingress filter installed by [Device::set_fwmark], matching all protocols.
+    #[cfg(feature = "netlink")]
+    fn fwmark_filter(index: u32) -> netlink::TrafficControlMessage {
+        let protocol = (libc::ETH_P_ALL as u16).to_be() as u32;
+        let info = FWMARK_FILTER_PRIORITY << 16 | protocol;
+        netlink::TrafficControlMessage::new(index, netlink::TC_H_CLSACT_INGRESS, 0, info)
+    }
+
+    /// Attach XDP program to the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `prog_fd` - File descriptor of loaded `BPF_PROG_TYPE_XDP` program. Kernel takes its own
+    ///   reference to the program, so descriptor can be closed after the call.
+    /// * `flags` - Attach mode and behaviour, either [XdpFlags] or just [XdpMode].
+    ///
+    /// # Remarks
+    ///
+    /// Requires `CAP_NET_ADMIN` and kernel with XDP support. Native (driver) mode support of tun/tap
+    /// differs between kernel versions, generic mode ([XdpMode::Generic]) works everywhere.
+    /// Program attached in given mode must be detached with the same mode flags.
+    #[cfg(feature = "netlink")]
+    pub fn attach_xdp<F: Into<XdpFlags>>(&self, prog_fd: RawFd, flags: F) -> Result<(), CreationError> {
+        let flags = flags.into();
+        netlink::change_link(libc::RTM_SETLINK, interface_index(&self.name)?, |message| {
+            message.nested(netlink::IFLA_XDP, |xdp| {
+                xdp.attribute(netlink::IFLA_XDP_FD, &prog_fd.to_ne_bytes())
+                    .attribute(netlink::IFLA_XDP_FLAGS, &flags.bits().to_ne_bytes());
+            });
+        })
+    }
+
+    /// Detach XDP program attached with [Device::attach_xdp], `flags` must contain the same mode
+    /// the program was attached with.
+    #[cfg(feature = "netlink")]
+    pub fn detach_xdp<F: Into<XdpFlags>>(&self, flags: F) -> Result<(), CreationError> {
+        self.attach_xdp(-1, flags)
+    }
+}
+
+impl Display for Device {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Debug for Device {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:?}Device({})", self.mode, self.name)
+    }
+}
+
+impl AsRawFd for Device {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl IntoRawFd for Device {
+    fn into_raw_fd(self) -> RawFd {
+        self.file.into_raw_fd()
+    }
+}
+
+impl Write for Device {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.check_shutdown(Shutdown::Write)?;
+        if self.strict_mtu {
+            self.validate_mtu_against_payload(buf.len().saturating_sub(self.framing_len()))
+                .map_err(|e| IoError::new(ErrorKind::InvalidInput, e))?;
+        }
+        let written = self.file.write(buf)?;
+        self.counters.count_written(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for Device {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.read_frame(&mut [IoSliceMut::new(buf)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capability_pre_check() {
+        let result = DeviceBuilder::new(DeviceMode::Tun).require_capability(true).open();
+        match has_net_admin() {
+            Some(false) => assert!(matches!(result, Err(CreationError::MissingCapability))),
+            _ => assert!(!matches!(result, Err(CreationError::MissingCapability))),
+        }
+    }
+
+    #[test]
+    fn mac_policy() {
+        assert_eq!(MacPolicy::Kernel.address().unwrap(), None);
+        let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        assert_eq!(MacPolicy::Fixed(mac).address().unwrap(), Some(mac));
+        assert!(matches!(MacPolicy::Fixed([0x01, 0, 0x5e, 0, 0, 1]).address(), Err(CreationError::InvalidMacAddress)));
+        assert!(matches!(MacPolicy::Fixed([0xff; 6]).address(), Err(CreationError::InvalidMacAddress)));
+        for _ in 0..16 {
+            let mac = MacPolicy::Random.address().unwrap().unwrap();
+            assert_eq!(mac[0] & 0x03, 0x02);
+        }
+    }
+
+    #[test]
+    fn features_cached_after_success() {
+        let cache = OnceLock::new();
+        assert_eq!(cached_features(&cache, || Err(CreationError::FileNotFound)), 0);
+        assert_eq!(cache.get(), None);
+        let features = (libc::IFF_TUN | libc::IFF_MULTI_QUEUE) as libc::c_uint;
+        assert_eq!(cached_features(&cache, || Ok::<_, CreationError>(features)), features);
+        assert_eq!(cached_features(&cache, || -> Result<_, CreationError> { panic!("features queried again") }), features);
+    }
+
+    /// Allocator recording allocations of at least `LARGE_ALLOCATION` bytes made by the current thread.
+    struct CountingAllocator;
+
+    const LARGE_ALLOCATION: usize = 1024;
+
+    thread_local! {
+        static LARGE_ALLOCATIONS: std::cell::RefCell<Option<Vec<usize>>> = const { std::cell::RefCell::new(None) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let ptr = std::alloc::System.alloc(layout);
+            if layout.size() >= LARGE_ALLOCATION {
+                let _ = LARGE_ALLOCATIONS.try_with(|allocations| {
+                    if let Ok(mut allocations) = allocations.try_borrow_mut() {
+                        // Recording is disabled while the vector itself grows
+                        if let Some(mut recorded) = allocations.take() {
+                            recorded.push(ptr as usize);
+                            *allocations = Some(recorded);
+                        }
+                    }
+                });
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Run `f` and return addresses of large allocations it made.
+    fn large_allocations<T>(f: impl FnOnce() -> T) -> (T, Vec<usize>) {
+        LARGE_ALLOCATIONS.with(|allocations| *allocations.borrow_mut() = Some(Vec::with_capacity(16)));
+        let result = f();
+        (result, LARGE_ALLOCATIONS.with(|allocations| allocations.borrow_mut().take().unwrap()))
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn packet_owned_without_copy() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).packet_info(true).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 11, 1), 24, true).unwrap();
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.send_to(b"payload", (Ipv4Addr::new(10, 92, 11, 2), 9)).unwrap();
+        loop {
+            let ((info, payload), allocations) = large_allocations(|| device.read_packet_owned().unwrap());
+            if !payload.ends_with(b"payload") {
+                continue;
+            }
+            assert_eq!(info.proto, libc::ETH_P_IP as u16);
+            assert_eq!(payload.len(), 20 + 8 + 7);
+            // Only the returned vector is large enough to hold the payload, so it was not copied
+            assert_eq!(allocations, [payload.as_ptr() as usize]);
+            break;
+        }
+    }
+
+    #[test]
+    fn sndbuf_bandwidth_delay() {
+        // 100 Mbit/s with 80 ms round trip is 1 MB in flight
+        assert_eq!(sndbuf_size(100_000_000, Duration::from_millis(80)), 1_000_000);
+        assert_eq!(sndbuf_size(1_000_000, Duration::from_millis(1)), MIN_SNDBUF as libc::c_int);
+        assert_eq!(sndbuf_size(0, Duration::from_secs(1)), MIN_SNDBUF as libc::c_int);
+        assert_eq!(sndbuf_size(u64::MAX, Duration::from_secs(3600)), libc::c_int::MAX);
+    }
+
+    #[test]
+    fn broadcast_of_network() {
+        let address = Ipv4Addr::new(192, 168, 7, 33);
+        assert_eq!(broadcast_address(address, Ipv4Addr::new(255, 255, 255, 0)), Ipv4Addr::new(192, 168, 7, 255));
+        assert_eq!(broadcast_address(address, Ipv4Addr::new(255, 255, 255, 224)), Ipv4Addr::new(192, 168, 7, 63));
+        assert_eq!(broadcast_address(address, Ipv4Addr::BROADCAST), address);
+    }
+
+    #[test]
+    fn netmask_prefix_length() {
+        assert_eq!(netmask_prefix(Ipv4Addr::new(255, 255, 255, 0)), Some(24));
+        assert_eq!(netmask_prefix(Ipv4Addr::new(255, 255, 240, 0)), Some(20));
+        assert_eq!(netmask_prefix(Ipv4Addr::BROADCAST), Some(32));
+        assert_eq!(netmask_prefix(Ipv4Addr::UNSPECIFIED), Some(0));
+        assert_eq!(netmask_prefix(Ipv4Addr::new(255, 0, 255, 0)), None);
+        assert_eq!(netmask_prefix(Ipv4Addr::new(0, 255, 255, 255)), None);
+    }
+
+    #[test]
+    fn flag_names() {
+        assert_eq!(format_flags(0), "");
+        assert_eq!(format_flags((libc::IFF_TAP | libc::IFF_NO_PI) as libc::c_short), "TAP|NO_PI");
+        assert_eq!(format_flags((libc::IFF_TUN | 0x0080) as libc::c_short), "TUN|0x80");
+        let vnet_hdr = libc::IFF_VNET_HDR as libc::c_short;
+        assert!(known_flags().iter().any(|(name, flag)| *name == "VNET_HDR" && *flag == vnet_hdr));
+        let all = known_flags().iter().fold(0, |flags, (_, flag)| flags | flag);
+        assert!(!format_flags(all).contains("0x"));
+    }
+
+    #[test]
+    fn mode_frame_sizes() {
+        assert_eq!(DeviceMode::Tun.default_mtu(), 1500);
+        assert_eq!(DeviceMode::Tap.default_mtu(), 1500);
+        assert_eq!(DeviceMode::Tun.max_frame_size(), 1500);
+        assert_eq!(DeviceMode::Tap.max_frame_size(), 1518);
+    }
+
+    #[test]
+    fn loopback_exists() {
+        assert!(name_exists("lo").unwrap());
+        assert!(!name_exists("tippytap-none").unwrap());
+        assert!(matches!(name_exists("l\0o"), Err(CreationError::InvalidName(_))));
+    }
+
+    fn sysfs_mtu(device: &Device) -> u32 {
+        let mtu = std::fs::read_to_string(format!("/sys/class/net/{}/mtu", device.name())).unwrap();
+        mtu.trim().parse().unwrap()
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn open_max_queues() {
+        assert!(max_queues() >= 1);
+        let mut builder = DeviceBuilder::new(DeviceMode::Tap);
+        let builder = builder.name("tippymq0");
+        let mut queues = Vec::new();
+        for _ in 0..max_queues() {
+            match builder.open_with_flags(libc::IFF_MULTI_QUEUE) {
+                Ok(queue) => queues.push(queue),
+                // Process ran out of file descriptors
+                Err(CreationError::UnableToOpenFile(_)) => break,
+                Err(e) => panic!("queue {} failed: {}", queues.len(), e),
+            }
+        }
+        assert!(!queues.is_empty());
+        if queues.len() == max_queues() {
+            assert!(matches!(builder.open_with_flags(libc::IFF_MULTI_QUEUE), Err(CreationError::IoctlError(_))));
+        }
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn refcounted_modes() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        let mut other = device.dup_for_child().unwrap();
+        let modes = DeviceFlags::PROMISC | DeviceFlags::ALLMULTI;
+        for holder in [&mut device, &mut other] {
+            holder.set_promiscuous_refcounted(true).unwrap();
+            holder.set_allmulti_refcounted(true).unwrap();
+        }
+        other.set_promiscuous_refcounted(false).unwrap();
+        assert!(device.sysfs_flags().unwrap().contains(modes));
+        other.set_promiscuous_refcounted(true).unwrap();
+        // Dropping the holder releases its memberships
+        drop(other);
+        assert!(device.sysfs_flags().unwrap().contains(modes));
+        device.set_promiscuous_refcounted(false).unwrap();
+        device.set_allmulti_refcounted(false).unwrap();
+        assert!(!device.sysfs_flags().unwrap().intersects(modes));
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn peek_then_read() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 12, 1), 24, true).unwrap();
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.send_to(b"peeked", (Ipv4Addr::new(10, 92, 12, 2), 9)).unwrap();
+        let mut header = [0u8; 20];
+        let mut frame = [0u8; 1500];
+        // Kernel may send its own packets before the sent one
+        loop {
+            let peeked = device.peek(&mut header).unwrap();
+            let mut again = [0u8; 20];
+            assert_eq!(device.peek(&mut again).unwrap(), peeked);
+            assert_eq!(again, header);
+            let read = device.read(&mut frame).unwrap();
+            assert_eq!(&frame[..peeked], &header[..peeked]);
+            if frame[..read].ends_with(b"peeked") {
+                assert_eq!(peeked, header.len());
+                break;
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn read_in_forked_child() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 13, 1), 24, true).unwrap();
+        let mut child_device = device.dup_for_child().unwrap();
+        child_device.set_nonblocking(true).unwrap();
+        assert_eq!(device.prepare_for_fork(), ForkDisposition::CloseOnExec);
+        assert_eq!(child_device.prepare_for_fork(), ForkDisposition::Inherited);
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.send_to(b"forked", (Ipv4Addr::new(10, 92, 13, 2), 9)).unwrap();
+        let mut frame = [0u8; 1500];
+        match unsafe { libc::fork() } {
+            -1 => panic!("fork failed: {}", IoError::last_os_error()),
+            0 => {
+                // Child does not allocate, it reads queued frames until the sent one is found
+                let mut status = 1;
+                while let Ok(read) = child_device.read(&mut frame) {
+                    if frame[..read].ends_with(b"forked") {
+                        status = 0;
+                        break;
+                    }
+                }
+                unsafe { libc::_exit(status) };
+            }
+            pid => {
+                let mut status = 0;
+                assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+                assert!(libc::WIFEXITED(status));
+                assert_eq!(libc::WEXITSTATUS(status), 0);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn tune_sndbuf_size() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        let size = device.tune_sndbuf(1_000_000_000, Duration::from_millis(10)).unwrap();
+        assert_eq!(size, 1_250_000);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn link_type_of_mode() {
+        let tap = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert_eq!(tap.link_type().unwrap(), libc::ARPHRD_ETHER);
+        let tun = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        assert_eq!(tun.link_type().unwrap(), libc::ARPHRD_NONE);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn setup_ipv4_address_route_up() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 14, 1), 24, true).unwrap();
+        assert_eq!(device.cidr().unwrap().as_deref(), Some("10.92.14.1/24"));
+        assert!(device.sysfs_flags().unwrap().contains(DeviceFlags::UP));
+        // Destination and mask in /proc/net/route are printed as integers in memory (network) order
+        let destination = format!("{:08X}", u32::from_ne_bytes([10, 92, 14, 0]));
+        let mask = format!("{:08X}", u32::from_ne_bytes([255, 255, 255, 0]));
+        let routes = std::fs::read_to_string("/proc/net/route").unwrap();
+        assert!(routes.lines().any(|route| {
+            let fields: Vec<&str> = route.split_whitespace().collect();
+            fields[0] == device.name() && fields[1] == destination && fields[7] == mask
+        }));
+        // Multicast address is refused, previous address is restored
+        assert!(device.setup_ipv4(Ipv4Addr::new(224, 0, 0, 1), 16, true).is_err());
+        assert_eq!(device.cidr().unwrap().as_deref(), Some("10.92.14.1/24"));
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn prefix_length() {
+        let device = DeviceBuilder::new(DeviceMode::Tun).packet_info(true).vnet_hdr(true).open().unwrap();
+        assert_eq!(device.prefix_len(), PacketInfo::SIZE + VirtioNetHeader::SIZE);
+        let device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert_eq!(device.prefix_len(), 0);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn broadcast_auto() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert!(device.set_broadcast_auto().is_err());
+        device.setup_ipv4(Ipv4Addr::new(10, 91, 3, 1), 20, false).unwrap();
+        device.set_broadcast_auto().unwrap();
+        let broadcast = get_broadcast_address(&get_control_socket().unwrap(), device.name()).unwrap();
+        assert_eq!(broadcast, Some(Ipv4Addr::new(10, 91, 15, 255)));
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn address_cidr() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert_eq!(device.cidr().unwrap(), None);
+        device.setup_ipv4(Ipv4Addr::new(10, 91, 3, 1), 20, false).unwrap();
+        assert_eq!(device.cidr().unwrap().as_deref(), Some("10.91.3.1/20"));
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn builder_mtu_and_txqueuelen() {
+        let device = DeviceBuilder::new(DeviceMode::Tap).mtu(9000).txqueuelen(50).open().unwrap();
+        assert_eq!(sysfs_mtu(&device), 9000);
+        assert_eq!(device.tx_queue_len().unwrap(), 50);
+        assert!(DeviceBuilder::new(DeviceMode::Tap).mtu(10).open().is_err());
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn sysfs_index() {
+        let device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert_eq!(device.index_sysfs().unwrap(), interface_index(device.name()).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn reset_to_defaults() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).mtu(4000).bring_up(true).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 0, 1), 24, true).unwrap();
+        device.reset().unwrap();
+        assert_eq!(sysfs_mtu(&device), DeviceMode::Tun.default_mtu());
+        assert_eq!(device.cidr().unwrap(), None);
+        let flags = device.sysfs_flags().unwrap();
+        assert!(!flags.contains(DeviceFlags::UP));
+        assert!(flags.contains(DeviceFlags::NOARP));
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn pointopoint_of_mode() {
+        assert!(DeviceBuilder::new(DeviceMode::Tun).open().unwrap().is_pointopoint().unwrap());
+        assert!(!DeviceBuilder::new(DeviceMode::Tap).open().unwrap().is_pointopoint().unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    #[cfg(feature = "etherparse")]
+    fn ip_packets_of_tun() {
+        let mut tap = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        assert_eq!(tap.ip_packets().err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).packet_info(true).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 42, 1), 24, true).unwrap();
+        let socket = std::net::UdpSocket::bind("10.92.42.1:0").unwrap();
+        socket.send_to(b"routed", (Ipv4Addr::new(10, 92, 42, 7), 9)).unwrap();
+        let (source, destination, packet) = device.ip_packets().unwrap()
+            .filter_map(Result::ok)
+            .find(|(_, _, packet)| packet.ends_with(b"routed"))
+            .unwrap();
+        assert_eq!(source, IpAddr::from([10, 92, 42, 1]));
+        assert_eq!(destination, IpAddr::from([10, 92, 42, 7]));
+        assert_eq!(packet[0] >> 4, 4);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    #[cfg(feature = "pcap-filter")]
+    fn pcap_filter_drops_non_ip() {
+        let mut tun = DeviceBuilder::new(DeviceMode::Tun).open().unwrap();
+        assert!(matches!(tun.attach_pcap_filter("ip"), Err(CreationError::IoctlError(nix::Error::Sys(Errno::EINVAL)))));
+
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).open().unwrap();
+        device.attach_pcap_filter("ip").unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 41, 1), 24, true).unwrap();
+        let socket = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        socket.set_broadcast(true).unwrap();
+        // Unicast needs ARP request (non-IP frame) first, broadcast is sent right away
+        socket.send_to(b"unicast", (Ipv4Addr::new(10, 92, 41, 2), 9)).unwrap();
+        socket.send_to(b"broadcast", (Ipv4Addr::new(10, 92, 41, 255), 9)).unwrap();
+        let mut frame = [0u8; 1518];
+        loop {
+            let read = device.read(&mut frame).unwrap();
+            assert_eq!(frame[12..14], (libc::ETH_P_IP as u16).to_be_bytes());
+            if frame[..read].ends_with(b"broadcast") {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn count_written_frames() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).bring_up(true).open().unwrap();
+        let frame = [0xffu8; 60];
+        device.write_all(&frame).unwrap();
+        device.write_padded(&frame[..42]).unwrap();
+        let counters = device.queue_counters();
+        assert_eq!(counters.written_frames, 2);
+        assert_eq!(counters.written_bytes, 120);
+        assert_eq!(counters.read_frames, 0);
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn count_per_descriptor() {
+        let mut device = DeviceBuilder::new(DeviceMode::Tap).bring_up(true).open().unwrap();
+        let mut duplicate = device.dup_for_child().unwrap();
+        duplicate.write_all(&[0xffu8; 60]).unwrap();
+        assert_eq!(duplicate.queue_counters().written_frames, 1);
+        assert_eq!(device.queue_counters().written_frames, 0);
+        device.write_all(&[0xffu8; 60]).unwrap();
+        assert_eq!(device.queue_counters().written_frames, 1);
+        assert_eq!(duplicate.queue_counters().written_frames, 1);
+    }
+
+    /// Bridge created for a test (`SIOCBRADDBR`) and removed when dropped.
+    #[cfg(feature = "netlink")]
+    struct TestBridge(&'static str);
+
+    #[cfg(feature = "netlink")]
+    impl TestBridge {
+        const SIOCBRADDBR: libc::c_ulong = 0x89a0;
+        const SIOCBRDELBR: libc::c_ulong = 0x89a1;
+
+        fn create(name: &'static str) -> Self {
+            let name_c = std::ffi::CString::new(name).unwrap();
+            let socket = get_control_socket().unwrap();
+            Errno::result(unsafe { libc::ioctl(socket.as_raw_fd(), Self::SIOCBRADDBR, name_c.as_ptr()) }).unwrap();
+            Self(name)
+        }
+    }
+
+    #[cfg(feature = "netlink")]
+    impl Drop for TestBridge {
+        fn drop(&mut self) {
+            let name = std::ffi::CString::new(self.0).unwrap();
+            let socket = get_control_socket().unwrap();
+            unsafe { libc::ioctl(socket.as_raw_fd(), Self::SIOCBRDELBR, name.as_ptr()) };
+        }
+    }
+
+    #[cfg(feature = "netlink")]
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn bridged_pair_mtu() {
+        let bridge = TestBridge::create("tippybr0");
+        let pair = BridgedPair::open(bridge.0).unwrap();
+        assert_eq!(pair.mtu_both().unwrap(), (1500, 1500));
+        set_mtu(&get_control_socket().unwrap(), pair.0.name(), 1400).unwrap();
+        assert_eq!(pair.mtu_both().unwrap(), (1400, 1500));
+        for device in [&pair.0, &pair.1] {
+            let master = std::fs::read_link(format!("/sys/class/net/{}/master", device.name())).unwrap();
+            assert!(master.ends_with(bridge.0));
+        }
     }
 }
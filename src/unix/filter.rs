@@ -0,0 +1,486 @@
+use crate::error::CreationError;
+use libc::{
+    sock_filter, BPF_ABS, BPF_B, BPF_H, BPF_IND, BPF_JEQ, BPF_JMP, BPF_JSET, BPF_K, BPF_LD, BPF_LDX, BPF_MSH,
+    BPF_RET, BPF_W,
+};
+use std::{convert::TryFrom, iter::Peekable, net::Ipv4Addr};
+
+/// Value returned by the filter for accepted frames, large enough to never truncate them.
+const ACCEPT: u32 = u32::MAX;
+/// Length of ethernet header, offset of network header in frames filtered by tap device.
+const NETWORK_OFFSET: u32 = 14;
+
+/// Single comparison of loaded value, the smallest unit of compiled filter.
+struct Test {
+    /// Instructions loading value to the accumulator.
+    loads: Vec<sock_filter>,
+    /// Jump instruction comparing accumulator with `k`, test passes if jump is taken.
+    jump: u32,
+    k: u32,
+}
+
+/// Parsed filter expression.
+enum Node {
+    Test(Test),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Src,
+    Dst,
+    Any,
+}
+
+fn stmt(code: u32, k: u32) -> sock_filter {
+    sock_filter { code: code as u16, jt: 0, jf: 0, k }
+}
+
+fn test(size: u32, offset: u32, jump: u32, k: u32) -> Node {
+    Node::Test(Test { loads: vec![stmt(BPF_LD | size | BPF_ABS, offset)], jump, k })
+}
+
+fn and(a: Node, b: Node) -> Node {
+    Node::And(Box::new(a), Box::new(b))
+}
+
+fn or(a: Node, b: Node) -> Node {
+    Node::Or(Box::new(a), Box::new(b))
+}
+
+fn not(a: Node) -> Node {
+    Node::Not(Box::new(a))
+}
+
+/// Match source or destination field at given offsets, according to direction qualifier.
+fn directed<F: Fn(u32) -> Node>(direction: Direction, src: u32, dst: u32, matches: F) -> Node {
+    match direction {
+        Direction::Src => matches(src),
+        Direction::Dst => matches(dst),
+        Direction::Any => or(matches(src), matches(dst)),
+    }
+}
+
+fn ethertype(kind: u32) -> Node {
+    test(BPF_H, 12, BPF_JEQ, kind)
+}
+
+fn ip() -> Node {
+    ethertype(libc::ETH_P_IP as u32)
+}
+
+fn ip6() -> Node {
+    ethertype(libc::ETH_P_IPV6 as u32)
+}
+
+/// Match IPv4 packet with given protocol.
+fn ip_protocol(protocol: u32) -> Node {
+    and(ip(), test(BPF_B, NETWORK_OFFSET + 9, BPF_JEQ, protocol))
+}
+
+/// Match IPv6 packet with given next header (extension headers are not followed).
+fn ip6_protocol(protocol: u32) -> Node {
+    and(ip6(), test(BPF_B, NETWORK_OFFSET + 6, BPF_JEQ, protocol))
+}
+
+fn port(protocols: &[u32], direction: Direction, port: u32) -> Node {
+    let any_of = |offset| {
+        protocols.iter().map(|protocol| test(BPF_B, offset, BPF_JEQ, *protocol)).reduce(or).unwrap()
+    };
+    // Transport header follows IPv4 header of variable length, loaded into index register
+    let ip_port = |offset| {
+        Node::Test(Test {
+            loads: vec![
+                stmt(BPF_LDX | BPF_B | BPF_MSH, NETWORK_OFFSET),
+                stmt(BPF_LD | BPF_H | BPF_IND, NETWORK_OFFSET + offset),
+            ],
+            jump: BPF_JEQ,
+            k: port,
+        })
+    };
+    // Only the first fragment contains transport header
+    let first_fragment = not(test(BPF_H, NETWORK_OFFSET + 6, BPF_JSET, 0x1fff));
+    let v4 = and(and(and(ip(), any_of(NETWORK_OFFSET + 9)), first_fragment), directed(direction, 0, 2, ip_port));
+    let ip6_port = |offset| test(BPF_H, NETWORK_OFFSET + 40 + offset, BPF_JEQ, port);
+    let v6 = and(and(ip6(), any_of(NETWORK_OFFSET + 6)), directed(direction, 0, 2, ip6_port));
+    or(v4, v6)
+}
+
+fn host(direction: Direction, address: Ipv4Addr) -> Node {
+    let address = u32::from(address);
+    let matches = |offset| test(BPF_W, NETWORK_OFFSET + offset, BPF_JEQ, address);
+    and(ip(), directed(direction, 12, 16, matches))
+}
+
+fn ether_host(direction: Direction, mac: [u8; 6]) -> Node {
+    let high = u16::from_be_bytes([mac[0], mac[1]]) as u32;
+    let low = u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]);
+    let matches = |offset| and(test(BPF_W, offset + 2, BPF_JEQ, low), test(BPF_H, offset, BPF_JEQ, high));
+    directed(direction, 6, 0, matches)
+}
+
+fn invalid<T>(message: String) -> Result<T, CreationError> {
+    Err(CreationError::InvalidFilter(message))
+}
+
+/// Recursive descent parser of filter expression, see [compile_pcap_filter] for the grammar.
+struct Parser<'a, I: Iterator<Item = &'a str>> {
+    tokens: Peekable<I>,
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Parser<'a, I> {
+    fn next(&mut self) -> Result<&'a str, CreationError> {
+        self.tokens.next().map_or_else(|| invalid("unexpected end of expression".to_string()), Ok)
+    }
+
+    fn next_if(&mut self, expected: &[&str]) -> bool {
+        self.tokens.next_if(|token| expected.contains(token)).is_some()
+    }
+
+    fn or(&mut self) -> Result<Node, CreationError> {
+        let mut node = self.and()?;
+        while self.next_if(&["or", "||"]) {
+            node = or(node, self.and()?);
+        }
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, CreationError> {
+        let mut node = self.not()?;
+        while self.next_if(&["and", "&&"]) {
+            node = and(node, self.not()?);
+        }
+        Ok(node)
+    }
+
+    fn not(&mut self) -> Result<Node, CreationError> {
+        if self.next_if(&["not", "!"]) {
+            return Ok(not(self.not()?));
+        }
+        if self.next_if(&["("]) {
+            let node = self.or()?;
+            return match self.next()? {
+                ")" => Ok(node),
+                token => invalid(format!("expected ')', found '{}'", token)),
+            };
+        }
+        self.primitive()
+    }
+
+    fn direction(&mut self) -> Direction {
+        if self.next_if(&["src"]) {
+            Direction::Src
+        } else if self.next_if(&["dst"]) {
+            Direction::Dst
+        } else {
+            Direction::Any
+        }
+    }
+
+    fn value<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, CreationError> {
+        let token = self.next()?;
+        token.parse().map_or_else(|_| invalid(format!("invalid {} '{}'", what, token)), Ok)
+    }
+
+    fn primitive(&mut self) -> Result<Node, CreationError> {
+        let protocols: &[u32] = match self.tokens.peek() {
+            Some(&"tcp") => &[libc::IPPROTO_TCP as u32],
+            Some(&"udp") => &[libc::IPPROTO_UDP as u32],
+            _ => &[libc::IPPROTO_TCP as u32, libc::IPPROTO_UDP as u32],
+        };
+        let token = self.next()?;
+        match token {
+            "ip" => Ok(ip()),
+            "ip6" => Ok(ip6()),
+            "arp" => Ok(ethertype(libc::ETH_P_ARP as u32)),
+            "icmp" => Ok(ip_protocol(libc::IPPROTO_ICMP as u32)),
+            "icmp6" => Ok(ip6_protocol(libc::IPPROTO_ICMPV6 as u32)),
+            "tcp" | "udp" if !matches!(self.tokens.peek(), Some(&"src") | Some(&"dst") | Some(&"port")) => {
+                let protocol = protocols[0];
+                Ok(or(ip_protocol(protocol), ip6_protocol(protocol)))
+            }
+            "tcp" | "udp" | "src" | "dst" | "port" | "host" => {
+                let direction = match token {
+                    "src" => Direction::Src,
+                    "dst" => Direction::Dst,
+                    _ => self.direction(),
+                };
+                let kind = if token == "port" || token == "host" { token } else { self.next()? };
+                match kind {
+                    "port" => Ok(port(protocols, direction, self.value::<u16>("port")? as u32)),
+                    "host" if token == "tcp" || token == "udp" => invalid(format!("'{} host' is not supported", token)),
+                    "host" => Ok(host(direction, self.value("IPv4 address")?)),
+                    kind => invalid(format!("expected 'port' or 'host', found '{}'", kind)),
+                }
+            }
+            "ether" => {
+                let direction = self.direction();
+                match self.next()? {
+                    "host" => (),
+                    kind => return invalid(format!("expected 'host', found '{}'", kind)),
+                }
+                let token = self.next()?;
+                let bytes = token.split(':').map(|byte| u8::from_str_radix(byte, 16).ok()).collect::<Option<Vec<_>>>();
+                match bytes {
+                    Some(bytes) if bytes.len() == 6 => {
+                        let mut mac = [0u8; 6];
+                        mac.copy_from_slice(&bytes);
+                        Ok(ether_host(direction, mac))
+                    }
+                    _ => invalid(format!("invalid MAC address '{}'", token)),
+                }
+            }
+            token => invalid(format!("unknown primitive '{}'", token)),
+        }
+    }
+}
+
+/// Append code of `node` to the program, which is built backwards (from the last instruction).
+/// Jumps lead to instruction at index `pass` or `fail` (counted from the end), if `node` matches or not.
+///
+/// # Returns
+///
+/// Index (counted from the end) of the first instruction of `node`.
+fn generate(program: &mut Vec<sock_filter>, node: &Node, pass: usize, fail: usize) -> Result<usize, CreationError> {
+    match node {
+        Node::And(a, b) => {
+            let b = generate(program, b, pass, fail)?;
+            generate(program, a, b, fail)
+        }
+        Node::Or(a, b) => {
+            let b = generate(program, b, pass, fail)?;
+            generate(program, a, pass, b)
+        }
+        Node::Not(a) => generate(program, a, fail, pass),
+        Node::Test(test) => {
+            let index = program.len();
+            // Jumps are relative to the next instruction and only forward
+            let offset = |target: usize| match u8::try_from(index - target - 1) {
+                Ok(offset) => Ok(offset),
+                Err(_) => invalid("expression is too complex, jump is too long".to_string()),
+            };
+            program.push(sock_filter {
+                code: (BPF_JMP | test.jump | BPF_K) as u16,
+                jt: offset(pass)?,
+                jf: offset(fail)?,
+                k: test.k,
+            });
+            program.extend(test.loads.iter().rev().copied());
+            Ok(program.len() - 1)
+        }
+    }
+}
+
+/// Compile tcpdump-style filter expression to classic BPF program for tap device, see [Device::attach_filter].
+///
+/// # Grammar
+///
+/// Primitives can be combined with `and` (`&&`), `or` (`||`), `not` (`!`) and parentheses, where `not`
+/// binds the strongest and `or` the weakest. Parentheses must be separated by spaces. Supported primitives:
+/// * `ip`, `ip6`, `arp` - Frames of given ethertype (untagged only).
+/// * `icmp`, `icmp6` - ICMP packets over IPv4 or ICMPv6 packets.
+/// * `tcp`, `udp` - Segments or datagrams over IPv4 or IPv6.
+/// * `[tcp|udp] [src|dst] port <port>` - Segments or datagrams with given source and/or destination port,
+///   over IPv4 (non-first fragments never match) or IPv6.
+/// * `[src|dst] host <ipv4 address>` - IPv4 packets with given source and/or destination address.
+/// * `ether [src|dst] host <mac address>` - Frames with given source and/or destination MAC address.
+///
+/// IPv6 extension headers are not followed, so IPv6 packets carrying them do not match transport primitives.
+///
+/// # Errors
+///
+/// If expression is invalid or unsupported, [CreationError::InvalidFilter](crate::error::CreationError) is returned.
+///
+/// [Device::attach_filter]: crate::prelude::Device::attach_filter
+pub fn compile_pcap_filter(expr: &str) -> Result<Vec<sock_filter>, CreationError> {
+    let mut parser = Parser { tokens: expr.split_whitespace().peekable() };
+    let node = parser.or()?;
+    if let Some(token) = parser.tokens.next() {
+        return invalid(format!("unexpected '{}'", token));
+    }
+
+    let mut program = vec![stmt(BPF_RET | BPF_K, 0), stmt(BPF_RET | BPF_K, ACCEPT)];
+    generate(&mut program, &node, 1, 0)?;
+    program.reverse();
+    if program.len() > libc::BPF_MAXINSNS as usize {
+        return invalid("expression is too complex, program is too long".to_string());
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interpret the subset of classic BPF emitted by the compiler, loads out of the frame reject it
+    /// (as kernel does).
+    fn run(program: &[sock_filter], frame: &[u8]) -> u32 {
+        let load = |offset: u32, size: u32| -> Option<u32> {
+            let len = match size {
+                BPF_W => 4,
+                BPF_H => 2,
+                _ => 1,
+            };
+            let bytes = frame.get(offset as usize..offset as usize + len)?;
+            Some(bytes.iter().fold(0, |value, byte| value << 8 | *byte as u32))
+        };
+        let (mut a, mut x, mut pc) = (0u32, 0u32, 0usize);
+        loop {
+            let insn = program[pc];
+            let code = insn.code as u32;
+            pc += 1;
+            match code & 0x07 {
+                BPF_RET => return insn.k,
+                BPF_LD => {
+                    let offset = if code & 0xe0 == BPF_IND { x + insn.k } else { insn.k };
+                    match load(offset, code & 0x18) {
+                        Some(value) => a = value,
+                        None => return 0,
+                    }
+                }
+                BPF_LDX => match load(insn.k, BPF_B) {
+                    Some(value) => x = (value & 0xf) * 4,
+                    None => return 0,
+                },
+                BPF_JMP => {
+                    let taken = match code & 0xf0 {
+                        BPF_JEQ => a == insn.k,
+                        BPF_JSET => a & insn.k != 0,
+                        _ => panic!("unexpected jump {:#x}", code),
+                    };
+                    pc += if taken { insn.jt } else { insn.jf } as usize;
+                }
+                _ => panic!("unexpected instruction {:#x}", code),
+            }
+        }
+    }
+
+    fn accepts(expr: &str, frame: &[u8]) -> bool {
+        run(&compile_pcap_filter(expr).unwrap(), frame) == ACCEPT
+    }
+
+    /// Ethernet frame with IPv4 packet carrying transport header with given ports.
+    fn ipv4_frame(protocol: u8, src: [u8; 4], dst: [u8; 4], ports: (u16, u16)) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x08, 0x00];
+        frame.extend_from_slice(&[0x46, 0, 0, 48, 0, 0, 0x40, 0, 64, protocol, 0, 0]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        // Options, so the transport header is not at fixed offset
+        frame.extend_from_slice(&[1, 1, 1, 0]);
+        frame.extend_from_slice(&ports.0.to_be_bytes());
+        frame.extend_from_slice(&ports.1.to_be_bytes());
+        frame.extend_from_slice(&[0; 16]);
+        frame
+    }
+
+    fn ipv6_frame(next_header: u8, ports: (u16, u16)) -> Vec<u8> {
+        let mut frame = vec![0x02, 0, 0, 0, 0, 2, 0x02, 0, 0, 0, 0, 1, 0x86, 0xdd];
+        frame.extend_from_slice(&[0x60, 0, 0, 0, 0, 20, next_header, 64]);
+        frame.extend_from_slice(&[0; 32]);
+        frame.extend_from_slice(&ports.0.to_be_bytes());
+        frame.extend_from_slice(&ports.1.to_be_bytes());
+        frame.extend_from_slice(&[0; 16]);
+        frame
+    }
+
+    const TCP: u8 = libc::IPPROTO_TCP as u8;
+    const UDP: u8 = libc::IPPROTO_UDP as u8;
+
+    #[test]
+    fn ethertypes() {
+        let tcp = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        assert!(accepts("ip", &tcp));
+        assert!(!accepts("ip6", &tcp));
+        assert!(!accepts("arp", &tcp));
+        assert!(accepts("ip6", &ipv6_frame(TCP, (1, 2))));
+        let mut arp = tcp.clone();
+        arp[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(accepts("arp", &arp));
+    }
+
+    #[test]
+    fn protocols_and_ports() {
+        let tcp = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        assert!(accepts("tcp", &tcp));
+        assert!(!accepts("udp", &tcp));
+        assert!(accepts("tcp port 80", &tcp));
+        assert!(accepts("tcp dst port 80", &tcp));
+        assert!(!accepts("tcp src port 80", &tcp));
+        assert!(accepts("src port 1234", &tcp));
+        assert!(!accepts("udp port 80", &tcp));
+        assert!(accepts("port 80", &ipv4_frame(UDP, [0; 4], [0; 4], (80, 53))));
+        assert!(!accepts("port 80", &ipv4_frame(libc::IPPROTO_ICMP as u8, [0; 4], [0; 4], (80, 80))));
+        assert!(accepts("icmp", &ipv4_frame(libc::IPPROTO_ICMP as u8, [0; 4], [0; 4], (0, 0))));
+    }
+
+    #[test]
+    fn non_first_fragment() {
+        let mut fragment = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        fragment[20..22].copy_from_slice(&[0x00, 0x10]);
+        assert!(accepts("tcp", &fragment));
+        assert!(!accepts("tcp port 80", &fragment));
+    }
+
+    #[test]
+    fn ipv6_ports() {
+        let udp = ipv6_frame(UDP, (5353, 53));
+        assert!(accepts("udp", &udp));
+        assert!(accepts("udp dst port 53", &udp));
+        assert!(!accepts("tcp port 53", &udp));
+        assert!(accepts("icmp6", &ipv6_frame(libc::IPPROTO_ICMPV6 as u8, (0, 0))));
+    }
+
+    #[test]
+    fn hosts() {
+        let tcp = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        assert!(accepts("host 10.0.0.2", &tcp));
+        assert!(accepts("src host 10.0.0.1", &tcp));
+        assert!(!accepts("dst host 10.0.0.1", &tcp));
+        assert!(!accepts("host 10.0.0.1", &ipv6_frame(TCP, (1, 2))));
+        assert!(accepts("ether src host 02:00:00:00:00:01", &tcp));
+        assert!(!accepts("ether dst host 02:00:00:00:00:01", &tcp));
+        assert!(accepts("ether host 02:00:00:00:00:02", &tcp));
+    }
+
+    #[test]
+    fn operators() {
+        let tcp = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        assert!(accepts("not udp", &tcp));
+        assert!(accepts("! arp && tcp", &tcp));
+        assert!(accepts("udp or tcp port 80", &tcp));
+        assert!(!accepts("tcp and not port 80", &tcp));
+        // `and` binds stronger than `or`
+        assert!(accepts("tcp port 80 or udp and arp", &tcp));
+        assert!(!accepts("( tcp port 80 or udp ) and arp", &tcp));
+        assert!(accepts("not ( udp || arp )", &tcp));
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let tcp = ipv4_frame(TCP, [10, 0, 0, 1], [10, 0, 0, 2], (1234, 80));
+        assert!(!accepts("tcp port 80", &tcp[..36]));
+        assert!(!accepts("ip", &[]));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expr in &[
+            "", "bogus", "tcp port", "port 65536", "host 10.0.0", "tcp host 10.0.0.1", "ether host 02:00",
+            "ether port 80", "( tcp", "tcp )", "tcp and", "src foo 1",
+        ] {
+            assert!(matches!(compile_pcap_filter(expr), Err(CreationError::InvalidFilter(_))), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn program_limits() {
+        let expr = vec!["tcp port 80"; 64].join(" or ");
+        assert!(matches!(compile_pcap_filter(&expr), Err(CreationError::InvalidFilter(_))));
+        let program = compile_pcap_filter("arp").unwrap();
+        assert_eq!(program.len(), 4);
+        assert_eq!(program[program.len() - 1].k, 0);
+        assert_eq!(program[program.len() - 2].k, ACCEPT);
+    }
+}
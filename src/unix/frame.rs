@@ -0,0 +1,796 @@
+use std::{
+    convert::TryInto,
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    net::Ipv4Addr,
+};
+use crate::unix::device::DeviceMode;
+#[cfg(feature = "etherparse")]
+use crate::error::ParseError;
+#[cfg(feature = "etherparse")]
+use etherparse::{InternetSlice, Ipv4HeaderSlice, Ipv6HeaderSlice, SlicedPacket, TransportSlice};
+#[cfg(feature = "etherparse")]
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Packet information (`struct tun_pi`), prepended by kernel to each frame if packet info is enabled.
+pub struct PacketInfo {
+    /// Frame flags, `TUN_PKT_STRIP` is set if frame did not fit into the read buffer.
+    pub flags: u16,
+    /// Protocol (EtherType) of the frame.
+    pub proto: u16,
+}
+
+impl PacketInfo {
+    /// Size of the packet info prefix.
+    pub const SIZE: usize = 4;
+
+    /// Parse packet info from the prefix of a frame, returns `None` if frame is too short.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let prefix: [u8; Self::SIZE] = frame.get(..Self::SIZE)?.try_into().ok()?;
+        Some(Self::from_bytes(prefix))
+    }
+
+    /// Create packet info for a frame without one, protocol is taken from the IP version (tun)
+    /// or the ethernet header (tap). If frame is too short, protocol is 0.
+    pub fn for_frame(frame: &[u8], mode: DeviceMode) -> Self {
+        let proto = match mode {
+            DeviceMode::Tun => match frame.first().map(|byte| byte >> 4) {
+                Some(4) => libc::ETH_P_IP as u16,
+                Some(6) => libc::ETH_P_IPV6 as u16,
+                _ => 0,
+            },
+            DeviceMode::Tap => frame.get(12..14).map_or(0, |ethertype| u16::from_be_bytes([ethertype[0], ethertype[1]])),
+        };
+        Self { flags: 0, proto }
+    }
+
+    /// Create packet info from its raw representation, flags are in native and protocol in network byte order.
+    pub fn from_bytes(bytes: [u8; Self::SIZE]) -> Self {
+        Self {
+            flags: u16::from_ne_bytes([bytes[0], bytes[1]]),
+            proto: u16::from_be_bytes([bytes[2], bytes[3]]),
+        }
+    }
+
+    /// Raw representation of the packet info, as expected by kernel.
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let flags = self.flags.to_ne_bytes();
+        let proto = self.proto.to_be_bytes();
+        [flags[0], flags[1], proto[0], proto[1]]
+    }
+
+    /// Returns protocol of the frame as [EtherType].
+    pub fn ethertype(&self) -> EtherType {
+        EtherType::from(self.proto)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Protocol (EtherType) of the frame, see [PacketInfo::ethertype]
+/// * `Ipv4` - IPv4 packet (`ETH_P_IP`)
+/// * `Ipv6` - IPv6 packet (`ETH_P_IPV6`)
+/// * `Arp` - ARP packet (`ETH_P_ARP`)
+/// * `Vlan` - 802.1Q tagged frame (`ETH_P_8021Q`)
+/// * `Other` - Any other protocol, contains its value
+pub enum EtherType {
+    Ipv4,
+    Ipv6,
+    Arp,
+    Vlan,
+    Other(u16),
+}
+
+impl From<u16> for EtherType {
+    fn from(proto: u16) -> Self {
+        match proto as libc::c_int {
+            libc::ETH_P_IP => EtherType::Ipv4,
+            libc::ETH_P_IPV6 => EtherType::Ipv6,
+            libc::ETH_P_ARP => EtherType::Arp,
+            libc::ETH_P_8021Q => EtherType::Vlan,
+            _ => EtherType::Other(proto),
+        }
+    }
+}
+
+impl From<EtherType> for u16 {
+    fn from(ethertype: EtherType) -> Self {
+        match ethertype {
+            EtherType::Ipv4 => libc::ETH_P_IP as u16,
+            EtherType::Ipv6 => libc::ETH_P_IPV6 as u16,
+            EtherType::Arp => libc::ETH_P_ARP as u16,
+            EtherType::Vlan => libc::ETH_P_8021Q as u16,
+            EtherType::Other(proto) => proto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// VLAN tag (802.1Q or 802.1ad) following source MAC address of ethernet frame.
+pub struct VlanTag {
+    /// Tag protocol identifier, `ETH_P_8021Q` or `ETH_P_8021AD`.
+    pub tpid: u16,
+    /// Priority code point (0-7).
+    pub pcp: u8,
+    /// Drop eligible indicator.
+    pub dei: bool,
+    /// VLAN identifier (0-4094).
+    pub vid: u16,
+}
+
+impl VlanTag {
+    /// Size of the VLAN tag.
+    pub const SIZE: usize = 4;
+    /// Largest valid VLAN identifier, 4095 is reserved.
+    pub const MAX_VID: u16 = 4094;
+
+    /// Parse VLAN tag of ethernet frame (without packet info prefix), returns `None` if frame is not tagged
+    /// or it is too short.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let tag = frame.get(12..12 + Self::SIZE)?;
+        let tpid = u16::from_be_bytes([tag[0], tag[1]]);
+        if tpid as libc::c_int != libc::ETH_P_8021Q && tpid as libc::c_int != libc::ETH_P_8021AD {
+            return None;
+        }
+        let tci = u16::from_be_bytes([tag[2], tag[3]]);
+        Some(Self {
+            tpid,
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0xfff,
+        })
+    }
+
+    /// Raw representation of the tag, TPID followed by TCI in network byte order.
+    pub fn to_bytes(self) -> [u8; Self::SIZE] {
+        let tpid = self.tpid.to_be_bytes();
+        let tci = ((self.pcp as u16 & 0x7) << 13 | (self.dei as u16) << 12 | self.vid & 0xfff).to_be_bytes();
+        [tpid[0], tpid[1], tci[0], tci[1]]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Virtio network header (`struct virtio_net_hdr`), prepended by kernel to each frame (after packet
+/// info) if device was opened with [DeviceBuilder::vnet_hdr](crate::unix::DeviceBuilder::vnet_hdr).
+pub struct VirtioNetHeader {
+    /// Header flags, `VIRTIO_NET_HDR_F_NEEDS_CSUM` is set if transport checksum is not finished.
+    pub flags: u8,
+    /// Type of segmentation offload (`VIRTIO_NET_HDR_GSO_*`), which frame requires.
+    pub gso_type: u8,
+    /// Length of the headers of the frame.
+    pub hdr_len: u16,
+    /// Size of the payload of the segments frame has to be split into.
+    pub gso_size: u16,
+    /// Offset, where checksummed data starts.
+    pub csum_start: u16,
+    /// Offset of the checksum field, relative to `csum_start`.
+    pub csum_offset: u16,
+}
+
+impl VirtioNetHeader {
+    /// Size of the virtio network header.
+    pub const SIZE: usize = 10;
+    /// Transport checksum must be finished (`VIRTIO_NET_HDR_F_NEEDS_CSUM`).
+    pub const F_NEEDS_CSUM: u8 = 1;
+    /// Frame does not require segmentation (`VIRTIO_NET_HDR_GSO_NONE`).
+    pub const GSO_NONE: u8 = 0;
+    /// Frame is TCP over IPv4 superframe (`VIRTIO_NET_HDR_GSO_TCPV4`).
+    pub const GSO_TCPV4: u8 = 1;
+    /// Frame is UDP superframe (`VIRTIO_NET_HDR_GSO_UDP`).
+    pub const GSO_UDP: u8 = 3;
+    /// Frame is TCP over IPv6 superframe (`VIRTIO_NET_HDR_GSO_TCPV6`).
+    pub const GSO_TCPV6: u8 = 4;
+    /// Segments have ECN congestion window reduced flag set (`VIRTIO_NET_HDR_GSO_ECN`).
+    pub const GSO_ECN: u8 = 0x80;
+
+    /// Parse virtio network header from the prefix of a frame, returns `None` if frame is too short.
+    /// Fields are in native byte order.
+    pub fn parse(frame: &[u8]) -> Option<Self> {
+        let header = frame.get(..Self::SIZE)?;
+        let field = |offset: usize| u16::from_ne_bytes([header[offset], header[offset + 1]]);
+        Some(Self {
+            flags: header[0],
+            gso_type: header[1],
+            hdr_len: field(2),
+            gso_size: field(4),
+            csum_start: field(6),
+            csum_offset: field(8),
+        })
+    }
+}
+
+/// Finish transport checksum of the packet, which was left unfinished by checksum offload
+/// (`VIRTIO_NET_HDR_F_NEEDS_CSUM`). Checksum field contains pseudo-header sum, so sum of the data
+/// from `csum_start` to the end of the packet is stored to it.
+///
+/// # Errors
+///
+/// If offsets do not fit into the packet, `InvalidData` error is returned.
+pub fn finish_checksum(packet: &mut [u8], header: &VirtioNetHeader) -> IoResult<()> {
+    let start = header.csum_start as usize;
+    let field = start + header.csum_offset as usize;
+    if field + 2 > packet.len() {
+        return Err(IoError::new(ErrorKind::InvalidData, "checksum offset is out of the packet"));
+    }
+    let sum = !checksum_fold(checksum_add(0, &packet[start..]));
+    packet[field..field + 2].copy_from_slice(&sum.to_be_bytes());
+    Ok(())
+}
+
+/// Split TCP segment (typically superframe created by segmentation offload) into segments, which fit
+/// into `mtu`, fixing their IP and TCP headers.
+///
+/// # Arguments
+///
+/// * `packet` - Ethernet frame (tap) or IP packet (tun), without packet info and virtio header.
+/// * `mode` - Mode of the device the packet belongs to.
+/// * `mtu` - Maximal size of IP packet of each segment.
+///
+/// # Remarks
+///
+/// Each segment gets the headers of the original packet, with IP length (and IPv4 identification),
+/// TCP sequence number and flags adjusted: `FIN` and `PSH` are kept only in the last segment and
+/// `CWR` only in the first. IPv4 header checksum and TCP checksum are computed from scratch.
+/// IPv6 extension headers are not supported.
+///
+/// # Errors
+///
+/// If packet is not TCP over IPv4 or IPv6 or is truncated, `InvalidData` error is returned.
+/// If headers do not fit into `mtu`, `InvalidInput` error is returned.
+pub fn segment_tcp(packet: &[u8], mode: DeviceMode, mtu: usize) -> IoResult<Vec<Vec<u8>>> {
+    let invalid = |message: &str| IoError::new(ErrorKind::InvalidData, message.to_string());
+    let byte = |offset: usize| packet.get(offset).copied().ok_or_else(|| invalid("packet is truncated"));
+
+    let ip = match mode {
+        DeviceMode::Tun => 0,
+        DeviceMode::Tap if [byte(12)?, byte(13)?] == [0x81, 0x00] => libc::ETH_HLEN as usize + 4,
+        DeviceMode::Tap => libc::ETH_HLEN as usize,
+    };
+    let (ipv4, ip_len) = match byte(ip)? >> 4 {
+        4 if byte(ip + 9)? == libc::IPPROTO_TCP as u8 => (true, (byte(ip)? & 0x0f) as usize * 4),
+        6 if byte(ip + 6)? == libc::IPPROTO_TCP as u8 => (false, 40),
+        _ => return Err(invalid("packet is not TCP over IPv4 or IPv6")),
+    };
+    let tcp = ip + ip_len;
+    let tcp_len = (byte(tcp + 12)? >> 4) as usize * 4;
+    let headers = tcp + tcp_len;
+    if headers > packet.len() || tcp_len < 20 {
+        return Err(invalid("packet is truncated"));
+    }
+    let mss = mtu.checked_sub(ip_len + tcp_len).filter(|mss| *mss > 0)
+        .ok_or_else(|| IoError::new(ErrorKind::InvalidInput, "headers do not fit into MTU"))?;
+
+    let read_u16 = |offset: usize| u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+    let id = read_u16(ip + 4);
+    let seq = u32::from_be_bytes([packet[tcp + 4], packet[tcp + 5], packet[tcp + 6], packet[tcp + 7]]);
+    let pseudo = if ipv4 {
+        checksum_add(0, &packet[ip + 12..ip + 20])
+    } else {
+        checksum_add(0, &packet[ip + 8..ip + 40])
+    };
+
+    let payload = &packet[headers..];
+    let count = payload.len().div_ceil(mss).max(1);
+    let mut segments = Vec::with_capacity(count);
+    for i in 0..count {
+        let chunk = &payload[(i * mss).min(payload.len())..((i + 1) * mss).min(payload.len())];
+        let mut segment = Vec::with_capacity(headers + chunk.len());
+        segment.extend_from_slice(&packet[..headers]);
+        segment.extend_from_slice(chunk);
+
+        if ipv4 {
+            let total = (ip_len + tcp_len + chunk.len()) as u16;
+            segment[ip + 2..ip + 4].copy_from_slice(&total.to_be_bytes());
+            segment[ip + 4..ip + 6].copy_from_slice(&id.wrapping_add(i as u16).to_be_bytes());
+            segment[ip + 10..ip + 12].copy_from_slice(&[0, 0]);
+            let sum = !checksum_fold(checksum_add(0, &segment[ip..tcp]));
+            segment[ip + 10..ip + 12].copy_from_slice(&sum.to_be_bytes());
+        } else {
+            let length = (tcp_len + chunk.len()) as u16;
+            segment[ip + 4..ip + 6].copy_from_slice(&length.to_be_bytes());
+        }
+
+        let offset = (i * mss) as u32;
+        segment[tcp + 4..tcp + 8].copy_from_slice(&seq.wrapping_add(offset).to_be_bytes());
+        if i + 1 != count {
+            // FIN and PSH
+            segment[tcp + 13] &= !0x09;
+        }
+        if i != 0 {
+            // CWR
+            segment[tcp + 13] &= !0x80;
+        }
+        segment[tcp + 16..tcp + 18].copy_from_slice(&[0, 0]);
+        let length = (tcp_len + chunk.len()) as u64;
+        let sum = !checksum_fold(checksum_add(pseudo + libc::IPPROTO_TCP as u64 + length, &segment[tcp..]));
+        segment[tcp + 16..tcp + 18].copy_from_slice(&sum.to_be_bytes());
+        segments.push(segment);
+    }
+    Ok(segments)
+}
+
+#[cfg(feature = "etherparse")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Result of checksum verification, see [verify_checksums]
+/// * `Valid` - All checksums are correct (or packet does not contain any)
+/// * `Invalid` - Some checksum is incorrect
+/// * `Unset` - Transport checksum was not computed, it is either zero or contains only pseudo-header
+///   sum left by checksum offload
+pub enum ChecksumStatus {
+    Valid,
+    Invalid,
+    Unset,
+}
+
+/// Add data as big endian 16 bit words to ones' complement sum.
+fn checksum_add(sum: u64, data: &[u8]) -> u64 {
+    data.chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], *word.get(1).unwrap_or(&0)]) as u64)
+        .fold(sum, |sum, word| sum + word)
+}
+
+/// Fold ones' complement sum into 16 bits.
+fn checksum_fold(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Update checksum of data, in which `old` bytes were replaced with `new` bytes of the same length (RFC 1624).
+fn checksum_update(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let sum = old.chunks(2)
+        .map(|word| !u16::from_be_bytes([word[0], word[1]]) as u64)
+        .fold(!checksum as u64, |sum, word| sum + word);
+    !checksum_fold(checksum_add(sum, new))
+}
+
+/// Replace source and destination MAC address of ethernet frame (without packet info prefix).
+/// Addresses set to `None` are kept. Ethernet frames read from tap do not contain frame check
+/// sequence, so no checksum has to be updated.
+///
+/// # Returns
+///
+/// False, if frame is shorter than ethernet header and was not changed.
+pub fn rewrite_mac(frame: &mut [u8], source: Option<[u8; 6]>, destination: Option<[u8; 6]>) -> bool {
+    if frame.len() < 14 {
+        return false;
+    }
+    if let Some(destination) = destination {
+        frame[0..6].copy_from_slice(&destination);
+    }
+    if let Some(source) = source {
+        frame[6..12].copy_from_slice(&source);
+    }
+    true
+}
+
+/// Replace source and destination address of IPv4 packet, either ethernet frame (including single
+/// VLAN tag) or IP packet, without packet info prefix. Addresses set to `None` are kept.
+///
+/// # Remarks
+///
+/// IPv4 header checksum is recomputed, TCP and UDP checksums are updated for the changed pseudo-header,
+/// so they stay valid (or invalid) as they were. UDP packets without checksum (zero) are left without it.
+/// Fragments other than the first one do not contain transport header, so only IP header is changed.
+///
+/// # Returns
+///
+/// False, if frame does not contain IPv4 packet (or it is truncated) and was not changed.
+pub fn rewrite_ipv4(
+    frame: &mut [u8],
+    mode: DeviceMode,
+    source: Option<Ipv4Addr>,
+    destination: Option<Ipv4Addr>,
+) -> bool {
+    let ethertype = |offset: usize| frame.get(offset..offset + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let ip = match mode {
+        DeviceMode::Tun => 0,
+        DeviceMode::Tap => match ethertype(12).map(|ethertype| ethertype as libc::c_int) {
+            Some(libc::ETH_P_IP) => 14,
+            Some(libc::ETH_P_8021Q) | Some(libc::ETH_P_8021AD) if ethertype(16) == Some(libc::ETH_P_IP as u16) => 18,
+            _ => return false,
+        },
+    };
+    let header_len = match frame.get(ip) {
+        Some(byte) if byte >> 4 == 4 && (byte & 0xf) >= 5 => (byte & 0xf) as usize * 4,
+        _ => return false,
+    };
+    if frame.len() < ip + header_len {
+        return false;
+    }
+    let old = frame[ip + 12..ip + 20].to_vec();
+    if let Some(source) = source {
+        frame[ip + 12..ip + 16].copy_from_slice(&source.octets());
+    }
+    if let Some(destination) = destination {
+        frame[ip + 16..ip + 20].copy_from_slice(&destination.octets());
+    }
+    frame[ip + 10..ip + 12].copy_from_slice(&[0, 0]);
+    let checksum = !checksum_fold(checksum_add(0, &frame[ip..ip + header_len]));
+    frame[ip + 10..ip + 12].copy_from_slice(&checksum.to_be_bytes());
+
+    let first_fragment = u16::from_be_bytes([frame[ip + 6], frame[ip + 7]]) & 0x1fff == 0;
+    let field = match frame[ip + 9] as libc::c_int {
+        libc::IPPROTO_TCP if first_fragment => ip + header_len + 16,
+        libc::IPPROTO_UDP if first_fragment => ip + header_len + 6,
+        _ => return true,
+    };
+    let checksum = match frame.get(field..field + 2) {
+        Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+        None => return true,
+    };
+    if frame[ip + 9] as libc::c_int == libc::IPPROTO_UDP && checksum == 0 {
+        return true;
+    }
+    let new = frame[ip + 12..ip + 20].to_vec();
+    let mut checksum = checksum_update(checksum, &old, &new);
+    if frame[ip + 9] as libc::c_int == libc::IPPROTO_UDP && checksum == 0 {
+        checksum = 0xffff;
+    }
+    frame[field..field + 2].copy_from_slice(&checksum.to_be_bytes());
+    true
+}
+
+/// Verify IPv4 header checksum and TCP or UDP checksum of the packet.
+///
+/// # Arguments
+///
+/// * `packet` - Ethernet frame read from tap or IP packet read from tun, without packet info prefix.
+/// * `mode` - Mode of the device the packet belongs to.
+///
+/// # Remarks
+///
+/// With checksum offload (`TUNSETOFFLOAD`), kernel leaves transport checksum unfinished, containing
+/// only sum of the pseudo-header, such packets are reported as [ChecksumStatus::Unset].
+/// Packets with other protocols than IP are reported as valid, as they do not have checksums to verify.
+///
+/// # Errors
+///
+/// If packet cannot be parsed or is truncated, [ParseError](crate::error::ParseError) is returned.
+#[cfg(feature = "etherparse")]
+pub fn verify_checksums(packet: &[u8], mode: DeviceMode) -> Result<ChecksumStatus, ParseError> {
+    let sliced = match mode {
+        DeviceMode::Tun => SlicedPacket::from_ip(packet)?,
+        DeviceMode::Tap => SlicedPacket::from_ethernet(packet)?,
+    };
+    let offset = |slice: &[u8]| slice.as_ptr() as usize - packet.as_ptr() as usize;
+
+    // Pseudo-header sum (without protocol and length) and end of the IP packet
+    let (pseudo, end) = match &sliced.ip {
+        Some(InternetSlice::Ipv4(header, _)) => {
+            if checksum_fold(checksum_add(0, header.slice())) != 0xffff {
+                return Ok(ChecksumStatus::Invalid);
+            }
+            let pseudo = checksum_add(checksum_add(0, &header.source()), &header.destination());
+            (pseudo, offset(header.slice()) + header.total_len() as usize)
+        }
+        Some(InternetSlice::Ipv6(header, _)) => {
+            let pseudo = checksum_add(checksum_add(0, &header.source()), &header.destination());
+            (pseudo, offset(header.slice()) + header.slice().len() + header.payload_length() as usize)
+        }
+        None => return Ok(ChecksumStatus::Valid),
+    };
+
+    let (start, protocol, checksum) = match &sliced.transport {
+        Some(TransportSlice::Tcp(tcp)) => (offset(tcp.slice()), 6u16, tcp.checksum()),
+        Some(TransportSlice::Udp(udp)) => (offset(udp.slice()), 17u16, udp.checksum()),
+        _ => return Ok(ChecksumStatus::Valid),
+    };
+    let segment = packet.get(start..end)
+        .ok_or(ParseError::UnexpectedEndOfSlice(end))?;
+
+    let pseudo = pseudo + protocol as u64 + segment.len() as u64;
+    if checksum_fold(checksum_add(pseudo, segment)) == 0xffff {
+        Ok(ChecksumStatus::Valid)
+    } else if checksum == 0 || checksum == checksum_fold(pseudo) {
+        Ok(ChecksumStatus::Unset)
+    } else {
+        Ok(ChecksumStatus::Invalid)
+    }
+}
+
+/// Returns source and destination address of IPv4 or IPv6 packet (e.g. read from tun device,
+/// without packet info prefix). Only IP header is parsed, payload is not checked.
+///
+/// # Errors
+///
+/// If packet is not IPv4 or IPv6 packet or its header is truncated, [ParseError](crate::error::ParseError)
+/// is returned.
+#[cfg(feature = "etherparse")]
+pub fn ip_addresses(packet: &[u8]) -> Result<(IpAddr, IpAddr), ParseError> {
+    match packet.first().map(|byte| byte >> 4) {
+        Some(4) => {
+            let header = Ipv4HeaderSlice::from_slice(packet)?;
+            Ok((header.source_addr().into(), header.destination_addr().into()))
+        }
+        Some(6) => {
+            let header = Ipv6HeaderSlice::from_slice(packet)?;
+            Ok((header.source_addr().into(), header.destination_addr().into()))
+        }
+        Some(version) => Err(ParseError::IpUnsupportedVersion(version)),
+        None => Err(ParseError::UnexpectedEndOfSlice(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: [u8; 4] = [10, 0, 0, 1];
+    const DESTINATION: [u8; 4] = [10, 0, 0, 2];
+
+    /// IPv4 packet with valid header checksum and no options.
+    fn ipv4(protocol: u8, transport: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0x12, 0x34, 0x40, 0, 64, protocol, 0, 0];
+        packet[2..4].copy_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+        packet.extend_from_slice(&SOURCE);
+        packet.extend_from_slice(&DESTINATION);
+        let sum = !checksum_fold(checksum_add(0, &packet));
+        packet[10..12].copy_from_slice(&sum.to_be_bytes());
+        packet.extend_from_slice(transport);
+        packet
+    }
+
+    /// Sum of IPv4 pseudo-header of the packet.
+    fn pseudo(packet: &[u8]) -> u64 {
+        let length = packet.len() as u64 - 20;
+        checksum_add(0, &packet[12..20]) + packet[9] as u64 + length
+    }
+
+    /// Set transport checksum of IPv4 packet at `field` to the valid value.
+    fn fill_checksum(packet: &mut [u8], field: usize) {
+        packet[field..field + 2].copy_from_slice(&[0, 0]);
+        let sum = !checksum_fold(checksum_add(pseudo(packet), &packet[20..]));
+        packet[field..field + 2].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    fn tcp_packet(payload_len: usize, flags: u8) -> Vec<u8> {
+        let mut tcp = vec![0x30, 0x39, 0x00, 0x50, 0, 0, 0x10, 0, 0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0];
+        tcp.extend((0..payload_len).map(|i| i as u8));
+        let mut packet = ipv4(libc::IPPROTO_TCP as u8, &tcp);
+        fill_checksum(&mut packet, 36);
+        packet
+    }
+
+    fn udp_packet(payload: &[u8]) -> Vec<u8> {
+        let mut udp = vec![0x13, 0x88, 0x00, 0x35, 0, 0, 0, 0];
+        udp[4..6].copy_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        udp.extend_from_slice(payload);
+        let mut packet = ipv4(libc::IPPROTO_UDP as u8, &udp);
+        fill_checksum(&mut packet, 26);
+        packet
+    }
+
+    fn ip_valid(packet: &[u8]) -> bool {
+        checksum_fold(checksum_add(0, &packet[..20])) == 0xffff
+    }
+
+    fn transport_valid(packet: &[u8]) -> bool {
+        checksum_fold(checksum_add(pseudo(packet), &packet[20..])) == 0xffff
+    }
+
+    #[test]
+    fn packet_info_round_trip() {
+        let info = PacketInfo { flags: 1, proto: libc::ETH_P_IPV6 as u16 };
+        let bytes = info.to_bytes();
+        assert_eq!(&bytes[2..], &[0x86, 0xdd]);
+        assert_eq!(PacketInfo::from_bytes(bytes), info);
+        assert_eq!(PacketInfo::parse(&bytes), Some(info));
+        assert_eq!(PacketInfo::parse(&bytes[..3]), None);
+    }
+
+    #[test]
+    fn packet_info_for_frame() {
+        assert_eq!(PacketInfo::for_frame(&[0x45], DeviceMode::Tun).proto, libc::ETH_P_IP as u16);
+        assert_eq!(PacketInfo::for_frame(&[0x60], DeviceMode::Tun).proto, libc::ETH_P_IPV6 as u16);
+        assert_eq!(PacketInfo::for_frame(&[], DeviceMode::Tun).proto, 0);
+        let mut frame = [0u8; 14];
+        frame[12..].copy_from_slice(&[0x08, 0x06]);
+        assert_eq!(PacketInfo::for_frame(&frame, DeviceMode::Tap).proto, libc::ETH_P_ARP as u16);
+        assert_eq!(PacketInfo::for_frame(&frame[..13], DeviceMode::Tap).proto, 0);
+    }
+
+    #[test]
+    fn ethertype_conversion() {
+        for ethertype in [EtherType::Ipv4, EtherType::Ipv6, EtherType::Arp, EtherType::Vlan, EtherType::Other(0x88cc)] {
+            assert_eq!(EtherType::from(u16::from(ethertype)), ethertype);
+        }
+        assert_eq!(EtherType::from(0x8100), EtherType::Vlan);
+        assert_eq!(EtherType::from(0x88a8), EtherType::Other(0x88a8));
+        let info = PacketInfo { flags: 0, proto: libc::ETH_P_ARP as u16 };
+        assert_eq!(info.ethertype(), EtherType::Arp);
+    }
+
+    #[test]
+    fn vlan_tag_round_trip() {
+        let tag = VlanTag { tpid: libc::ETH_P_8021Q as u16, pcp: 5, dei: true, vid: 100 };
+        let bytes = tag.to_bytes();
+        assert_eq!(bytes, [0x81, 0x00, 0xb0, 0x64]);
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&bytes);
+        assert_eq!(VlanTag::parse(&frame), Some(tag));
+        let ad = VlanTag { tpid: libc::ETH_P_8021AD as u16, pcp: 0, dei: false, vid: VlanTag::MAX_VID };
+        frame[12..].copy_from_slice(&ad.to_bytes());
+        assert_eq!(VlanTag::parse(&frame), Some(ad));
+    }
+
+    #[test]
+    fn vlan_tag_untagged() {
+        let mut frame = vec![0u8; 16];
+        frame[12..14].copy_from_slice(&[0x08, 0x00]);
+        assert_eq!(VlanTag::parse(&frame), None);
+        frame[12..14].copy_from_slice(&[0x81, 0x00]);
+        assert_eq!(VlanTag::parse(&frame[..15]), None);
+    }
+
+    #[test]
+    fn virtio_header_parse() {
+        let mut bytes = vec![1, VirtioNetHeader::GSO_TCPV4];
+        for field in &[54u16, 1448, 34, 16] {
+            bytes.extend_from_slice(&field.to_ne_bytes());
+        }
+        let header = VirtioNetHeader::parse(&bytes).unwrap();
+        assert_eq!(header, VirtioNetHeader {
+            flags: VirtioNetHeader::F_NEEDS_CSUM,
+            gso_type: VirtioNetHeader::GSO_TCPV4,
+            hdr_len: 54,
+            gso_size: 1448,
+            csum_start: 34,
+            csum_offset: 16,
+        });
+        assert_eq!(VirtioNetHeader::parse(&bytes[..9]), None);
+    }
+
+    #[test]
+    fn finish_partial_checksum() {
+        let mut packet = tcp_packet(101, 0x18);
+        // Checksum offload leaves sum of the pseudo-header in the checksum field
+        let partial = checksum_fold(pseudo(&packet));
+        packet[36..38].copy_from_slice(&partial.to_be_bytes());
+        assert!(!transport_valid(&packet));
+        let header = VirtioNetHeader { csum_start: 20, csum_offset: 16, ..VirtioNetHeader::default() };
+        finish_checksum(&mut packet, &header).unwrap();
+        assert!(transport_valid(&packet));
+        let header = VirtioNetHeader { csum_start: packet.len() as u16, ..header };
+        assert_eq!(finish_checksum(&mut packet, &header).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn segment_tcp_ipv4() {
+        // FIN, PSH, ACK and CWR
+        let packet = tcp_packet(3000, 0x99);
+        let segments = segment_tcp(&packet, DeviceMode::Tun, 1500).unwrap();
+        assert_eq!(segments.len(), 3);
+        let mut payload = Vec::new();
+        for (i, segment) in segments.iter().enumerate() {
+            assert!(segment.len() <= 1500);
+            assert_eq!(u16::from_be_bytes([segment[2], segment[3]]) as usize, segment.len());
+            assert_eq!(u16::from_be_bytes([segment[4], segment[5]]), 0x1234 + i as u16);
+            let seq = u32::from_be_bytes([segment[24], segment[25], segment[26], segment[27]]);
+            assert_eq!(seq, 0x1000 + payload.len() as u32);
+            assert!(ip_valid(segment));
+            assert!(transport_valid(segment));
+            let flags = segment[33];
+            assert_eq!(flags & 0x09 != 0, i == 2);
+            assert_eq!(flags & 0x80 != 0, i == 0);
+            payload.extend_from_slice(&segment[40..]);
+        }
+        assert_eq!(payload, &packet[40..]);
+    }
+
+    #[test]
+    fn segment_tcp_tap_vlan() {
+        let mut frame = vec![0xff; 12];
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a, 0x08, 0x00]);
+        frame.extend_from_slice(&tcp_packet(1000, 0x10));
+        let segments = segment_tcp(&frame, DeviceMode::Tap, 576).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert!(segments.iter().all(|segment| ip_valid(&segment[18..]) && transport_valid(&segment[18..])));
+        assert_eq!(segments[1].len(), 18 + 40 + 1000 - 536);
+    }
+
+    #[test]
+    fn segment_tcp_errors() {
+        let packet = udp_packet(&[0; 16]);
+        assert_eq!(segment_tcp(&packet, DeviceMode::Tun, 1500).unwrap_err().kind(), ErrorKind::InvalidData);
+        let packet = tcp_packet(100, 0x10);
+        assert_eq!(segment_tcp(&packet[..30], DeviceMode::Tun, 1500).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(segment_tcp(&packet, DeviceMode::Tun, 40).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(segment_tcp(&packet, DeviceMode::Tun, 1500).unwrap(), vec![packet]);
+    }
+
+    #[test]
+    fn checksum_incremental_update() {
+        let packet = udp_packet(b"checksum");
+        let checksum = u16::from_be_bytes([packet[26], packet[27]]);
+        let mut changed = packet.clone();
+        changed[12..16].copy_from_slice(&[192, 168, 1, 1]);
+        let updated = checksum_update(checksum, &packet[12..20], &changed[12..20]);
+        changed[26..28].copy_from_slice(&updated.to_be_bytes());
+        assert!(transport_valid(&changed));
+    }
+
+    #[test]
+    fn rewrite_ipv4_tun() {
+        let source = Ipv4Addr::new(172, 16, 0, 1);
+        let destination = Ipv4Addr::new(172, 16, 0, 2);
+        for mut packet in [tcp_packet(33, 0x18), udp_packet(b"odd length")] {
+            assert!(rewrite_ipv4(&mut packet, DeviceMode::Tun, Some(source), Some(destination)));
+            assert_eq!(&packet[12..16], &source.octets());
+            assert_eq!(&packet[16..20], &destination.octets());
+            assert!(ip_valid(&packet));
+            assert!(transport_valid(&packet));
+        }
+    }
+
+    #[test]
+    fn rewrite_ipv4_udp_without_checksum() {
+        let mut packet = udp_packet(b"data");
+        packet[26..28].copy_from_slice(&[0, 0]);
+        assert!(rewrite_ipv4(&mut packet, DeviceMode::Tun, None, Some(Ipv4Addr::new(1, 2, 3, 4))));
+        assert_eq!(&packet[26..28], &[0, 0]);
+        assert_eq!(&packet[12..16], &SOURCE);
+        assert!(ip_valid(&packet));
+    }
+
+    #[test]
+    fn rewrite_ipv4_tap() {
+        let mut frame = vec![0xff; 12];
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x0a, 0x08, 0x00]);
+        frame.extend_from_slice(&tcp_packet(10, 0x10));
+        assert!(rewrite_ipv4(&mut frame, DeviceMode::Tap, Some(Ipv4Addr::new(8, 8, 8, 8)), None));
+        assert!(ip_valid(&frame[18..]));
+        assert!(transport_valid(&frame[18..]));
+
+        let mut arp = vec![0u8; 42];
+        arp[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(!rewrite_ipv4(&mut arp, DeviceMode::Tap, Some(Ipv4Addr::new(8, 8, 8, 8)), None));
+        let mut ipv6 = vec![0x60; 40];
+        assert!(!rewrite_ipv4(&mut ipv6, DeviceMode::Tun, Some(Ipv4Addr::new(8, 8, 8, 8)), None));
+        let mut truncated = tcp_packet(0, 0x10)[..19].to_vec();
+        assert!(!rewrite_ipv4(&mut truncated, DeviceMode::Tun, Some(Ipv4Addr::new(8, 8, 8, 8)), None));
+    }
+
+    #[test]
+    fn rewrite_mac_addresses() {
+        let mut frame = [0u8; 14];
+        assert!(rewrite_mac(&mut frame, Some([2; 6]), None));
+        assert_eq!(&frame[..6], &[0; 6]);
+        assert_eq!(&frame[6..12], &[2; 6]);
+        assert!(rewrite_mac(&mut frame, None, Some([4; 6])));
+        assert_eq!(&frame[..6], &[4; 6]);
+        assert!(!rewrite_mac(&mut frame[..13], Some([6; 6]), Some([6; 6])));
+    }
+
+    #[cfg(feature = "etherparse")]
+    #[test]
+    fn verify_packet_checksums() {
+        let mut packet = udp_packet(b"payload");
+        assert_eq!(verify_checksums(&packet, DeviceMode::Tun).unwrap(), ChecksumStatus::Valid);
+        packet[26..28].copy_from_slice(&[0, 0]);
+        assert_eq!(verify_checksums(&packet, DeviceMode::Tun).unwrap(), ChecksumStatus::Unset);
+        packet[27] = 1;
+        assert_eq!(verify_checksums(&packet, DeviceMode::Tun).unwrap(), ChecksumStatus::Invalid);
+        let mut packet = tcp_packet(8, 0x10);
+        packet[10] ^= 0xff;
+        assert_eq!(verify_checksums(&packet, DeviceMode::Tun).unwrap(), ChecksumStatus::Invalid);
+    }
+
+    #[cfg(feature = "etherparse")]
+    #[test]
+    fn packet_ip_addresses() {
+        let (source, destination) = ip_addresses(&udp_packet(&[])).unwrap();
+        assert_eq!(source, IpAddr::from(SOURCE));
+        assert_eq!(destination, IpAddr::from(DESTINATION));
+        let source = std::net::Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let destination = std::net::Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+        // No payload (next header 59)
+        let mut packet = vec![0x60, 0, 0, 0, 0, 0, 59, 255];
+        packet.extend_from_slice(&source.octets());
+        packet.extend_from_slice(&destination.octets());
+        assert_eq!(ip_addresses(&packet).unwrap(), (IpAddr::from(source), IpAddr::from(destination)));
+        assert!(ip_addresses(&packet[..39]).is_err());
+        assert!(matches!(ip_addresses(&[0x50]), Err(ParseError::IpUnsupportedVersion(5))));
+        assert!(ip_addresses(&[]).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    error::CreationError,
+    unix::netlink::{self, InterfaceInfoMessage, NetlinkSocket},
+};
+use nix::errno::Errno;
+use std::{collections::VecDeque, ffi::CStr, os::unix::io::AsRawFd};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Change of the device reported by [LinkEvents]
+/// * `Up` - Device was brought up
+/// * `Down` - Device was brought down
+/// * `Renamed` - Device was renamed, contains the new name
+/// * `Removed` - Device was removed, it is the last event
+pub enum LinkEvent {
+    Up,
+    Down,
+    Renamed(String),
+    Removed,
+}
+
+/// Link notifications of single device, translated into [LinkEvent]s.
+#[derive(Debug)]
+pub(crate) struct LinkState {
+    index: u32,
+    name: String,
+    pending: VecDeque<LinkEvent>,
+    removed: bool,
+}
+
+impl LinkState {
+    /// Handle notification of given type, ignoring notifications of other devices.
+    fn handle(&mut self, kind: u16, payload: &[u8]) {
+        let info = match netlink::header::<InterfaceInfoMessage>(payload) {
+            Some(info) if info.index as u32 == self.index && !self.removed => info,
+            _ => return,
+        };
+        if kind == libc::RTM_DELLINK {
+            self.removed = true;
+            self.pending.push_back(LinkEvent::Removed);
+            return;
+        }
+        if kind != libc::RTM_NEWLINK {
+            return;
+        }
+        if info.change & libc::IFF_UP as libc::c_uint != 0 {
+            let up = info.flags & libc::IFF_UP as libc::c_uint != 0;
+            self.pending.push_back(if up { LinkEvent::Up } else { LinkEvent::Down });
+        }
+        let name = netlink::attributes::<InterfaceInfoMessage>(payload)
+            .find(|(kind, _)| *kind == libc::IFLA_IFNAME)
+            .and_then(|(_, data)| CStr::from_bytes_until_nul(data).ok()?.to_str().ok().map(String::from));
+        if let Some(name) = name.filter(|name| *name != self.name) {
+            self.name = name.clone();
+            self.pending.push_back(LinkEvent::Renamed(name));
+        }
+    }
+
+    /// Receive all pending notifications from `socket` without waiting.
+    pub(crate) fn receive(&mut self, socket: &mut NetlinkSocket) -> Result<(), CreationError> {
+        socket.notifications(|kind, payload| self.handle(kind, payload))
+    }
+
+    /// Returns next event, or `None` if there is none yet. After [LinkEvent::Removed], `Some(None)` is returned.
+    pub(crate) fn next_event(&mut self) -> Option<Option<LinkEvent>> {
+        match self.pending.pop_front() {
+            Some(event) => Some(Some(event)),
+            None if self.removed => Some(None),
+            None => None,
+        }
+    }
+}
+
+/// Blocking iterator over changes of the device (link state, name and removal), created with
+/// [Device::watch](crate::unix::Device::watch).
+///
+/// # Remarks
+///
+/// Route netlink socket subscribed to link notifications (`RTMGRP_LINK`) is opened when iterator is
+/// created, so only later changes are reported. Iterator waits for the next change without timeout and
+/// ends after [LinkEvent::Removed]. Errors of receiving notifications (e.g. `ENOBUFS` when notifications
+/// were lost, because they were not read quickly enough) are yielded, but do not end the iteration.
+#[derive(Debug)]
+pub struct LinkEvents {
+    pub(crate) socket: NetlinkSocket,
+    pub(crate) state: LinkState,
+}
+
+impl LinkEvents {
+    pub(crate) fn new(index: u32, name: &str) -> Result<Self, CreationError> {
+        Ok(Self {
+            socket: NetlinkSocket::subscribe(libc::RTMGRP_LINK as u32)?,
+            state: LinkState {
+                index,
+                name: name.to_string(),
+                pending: VecDeque::new(),
+                removed: false,
+            },
+        })
+    }
+
+    /// Wait until notification is received.
+    fn wait(&self) -> Result<(), CreationError> {
+        let mut fd = libc::pollfd { fd: self.socket.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        loop {
+            match Errno::result(unsafe { libc::poll(&mut fd, 1, -1) }) {
+                Err(nix::Error::Sys(Errno::EINTR)) => (),
+                result => return result.map(|_| ()).map_err(CreationError::from),
+            }
+        }
+    }
+}
+
+impl Iterator for LinkEvents {
+    type Item = Result<LinkEvent, CreationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.state.next_event() {
+                return event.map(Ok);
+            }
+            if let Err(e) = self.wait().and_then(|_| self.state.receive(&mut self.socket)) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
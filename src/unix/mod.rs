@@ -3,5 +3,22 @@ mod r#async;
 
 mod utils;
 mod device;
+mod frame;
+mod stats;
+mod pcap;
+#[cfg(feature = "netlink")]
+mod netlink;
+#[cfg(feature = "netlink")]
+mod link;
+#[cfg(feature = "pcap-filter")]
+mod filter;
 
-pub use device::*;
\ No newline at end of file
+pub use device::*;
+pub use frame::*;
+pub use stats::*;
+#[cfg(feature = "netlink")]
+pub use link::*;
+#[cfg(feature = "pcap-filter")]
+pub use filter::*;
+#[cfg(feature = "async")]
+pub use r#async::*;
\ No newline at end of file
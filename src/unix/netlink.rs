@@ -0,0 +1,450 @@
+use libc::*;
+use nix::{
+    errno::Errno,
+    sys::socket::{recv, send, MsgFlags},
+};
+use crate::error::CreationError;
+use std::{
+    fs::File,
+    mem::size_of,
+    os::unix::io::{AsRawFd, FromRawFd, RawFd},
+    ptr, slice,
+};
+
+/// Netlink messages and attributes are aligned to 4 bytes.
+const NETLINK_ALIGN: usize = 4;
+/// Size of the receive buffer, large enough to hold any single rtnetlink datagram.
+const RECEIVE_BUFFER_SIZE: usize = 64 * 1024;
+
+/// `IFLA_XDP` nested attribute and its members, missing in libc.
+pub const IFLA_XDP: c_ushort = 43;
+pub const IFLA_XDP_FD: c_ushort = 1;
+pub const IFLA_XDP_FLAGS: c_ushort = 3;
+
+/// Flags of new object requests, missing in libc.
+pub const NLM_F_REPLACE: c_int = 0x100;
+pub const NLM_F_EXCL: c_int = 0x200;
+pub const NLM_F_CREATE: c_int = 0x400;
+
+/// Handle of `clsact` qdisc and its ingress hook, used as parent of ingress filters.
+pub const TC_H_CLSACT: u32 = 0xffff_fff1;
+pub const TC_H_CLSACT_INGRESS: u32 = 0xffff_fff2;
+/// `u32` classifier attributes.
+pub const TCA_U32_SEL: c_ushort = 5;
+pub const TCA_U32_ACT: c_ushort = 7;
+/// Selector flag, marking match as final, so actions are executed.
+pub const TC_U32_TERMINAL: c_uchar = 1;
+/// Action attributes, nested in list of actions under their order.
+pub const TCA_ACT_KIND: c_ushort = 1;
+pub const TCA_ACT_OPTIONS: c_ushort = 2;
+/// `skbedit` action attributes.
+pub const TCA_SKBEDIT_PARMS: c_ushort = 2;
+pub const TCA_SKBEDIT_MARK: c_ushort = 5;
+/// Action verdict continuing with the next action or classification result.
+pub const TC_ACT_PIPE: c_int = 3;
+
+/// Route metric (member of `RTA_METRICS`) holding MTU of the route.
+pub const RTAX_MTU: c_ushort = 2;
+
+/// Address attributes and flags (`IFA_*`), missing in libc.
+pub const IFA_ADDRESS: c_ushort = 1;
+pub const IFA_LOCAL: c_ushort = 2;
+pub const IFA_FLAGS: c_ushort = 8;
+pub const IFA_F_DADFAILED: u32 = 0x08;
+pub const IFA_F_TENTATIVE: u32 = 0x40;
+
+fn align(len: usize) -> usize {
+    (len + NETLINK_ALIGN - 1) & !(NETLINK_ALIGN - 1)
+}
+
+/// Represent plain C structure as its raw bytes.
+pub fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    unsafe {
+        slice::from_raw_parts(value as *const T as *const u8, size_of::<T>())
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Header of link messages (`RTM_*LINK`), identifying interface the message is about.
+pub struct InterfaceInfoMessage {
+    pub family: c_uchar,
+    pad: c_uchar,
+    pub link_type: c_ushort,
+    pub index: c_int,
+    pub flags: c_uint,
+    pub change: c_uint,
+}
+
+impl InterfaceInfoMessage {
+    /// Create header addressing interface with given index.
+    pub fn new(index: u32) -> Self {
+        Self {
+            family: AF_UNSPEC as c_uchar,
+            index: index as c_int,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Header of neighbour messages (`RTM_*NEIGH`), identifying neighbour table entry.
+pub struct NeighbourMessage {
+    pub family: c_uchar,
+    pad1: c_uchar,
+    pad2: c_ushort,
+    pub index: c_int,
+    pub state: c_ushort,
+    pub flags: c_uchar,
+    pub kind: c_uchar,
+}
+
+impl NeighbourMessage {
+    /// Create header addressing entries of interface with given index and address family.
+    pub fn new(family: c_int, index: u32) -> Self {
+        Self {
+            family: family as c_uchar,
+            index: index as c_int,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Header of address messages (`RTM_*ADDR`), identifying address of interface.
+pub struct AddressMessage {
+    pub family: c_uchar,
+    pub prefix_len: c_uchar,
+    pub flags: c_uchar,
+    pub scope: c_uchar,
+    pub index: c_uint,
+}
+
+impl AddressMessage {
+    /// Create header addressing addresses of interface with given index and address family.
+    pub fn new(family: c_int, index: u32) -> Self {
+        Self {
+            family: family as c_uchar,
+            index,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Header of route messages (`RTM_*ROUTE`), identifying route in routing table.
+pub struct RouteMessage {
+    pub family: c_uchar,
+    pub dst_len: c_uchar,
+    pub src_len: c_uchar,
+    pub tos: c_uchar,
+    pub table: c_uchar,
+    pub protocol: c_uchar,
+    pub scope: c_uchar,
+    pub kind: c_uchar,
+    pub flags: c_uint,
+}
+
+impl RouteMessage {
+    /// Create header addressing routes of given address family.
+    pub fn new(family: c_int) -> Self {
+        Self {
+            family: family as c_uchar,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Header of traffic control messages (`RTM_*QDISC`, `RTM_*TFILTER`), identifying qdisc or filter of interface.
+pub struct TrafficControlMessage {
+    pub family: c_uchar,
+    pad1: c_uchar,
+    pad2: c_ushort,
+    pub index: c_int,
+    pub handle: c_uint,
+    pub parent: c_uint,
+    pub info: c_uint,
+}
+
+impl TrafficControlMessage {
+    /// Create header addressing object with given `handle` under `parent` of interface with given index.
+    /// For filters, `info` contains priority in upper and protocol (network byte order) in lower 16 bits.
+    pub fn new(index: u32, parent: u32, handle: u32, info: u32) -> Self {
+        Self {
+            family: AF_UNSPEC as c_uchar,
+            index: index as c_int,
+            handle,
+            parent,
+            info,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Selector of `u32` classifier (`tc_u32_sel`) with single key, matching `value` masked by `mask`
+/// at `offset` of network header.
+pub struct U32Selector {
+    pub flags: c_uchar,
+    pub offshift: c_uchar,
+    pub keys: c_uchar,
+    pub offmask: u16,
+    pub off: u16,
+    pub offoff: i16,
+    pub hoff: i16,
+    pub hmask: u32,
+    pub mask: u32,
+    pub value: u32,
+    pub offset: c_int,
+    pub offset_mask: c_int,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+/// Parameters of `skbedit` action (`tc_skbedit`).
+pub struct SkbEditParameters {
+    pub index: u32,
+    pub capab: u32,
+    pub action: c_int,
+    pub refcnt: c_int,
+    pub bindcnt: c_int,
+}
+
+/// Netlink message being built, consisting of family specific header followed by attributes.
+pub struct NetlinkMessage {
+    kind: u16,
+    flags: u16,
+    payload: Vec<u8>,
+}
+
+impl NetlinkMessage {
+    /// Create new message.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - Type of the message (e.g. `RTM_NEWLINK`).
+    /// * `flags` - Additional `NLM_F_*` flags, request and acknowledge flags are set when sending.
+    /// * `header` - Family specific header (e.g. [InterfaceInfoMessage]).
+    pub fn new<H: Copy>(kind: u16, flags: c_int, header: &H) -> Self {
+        let mut ret = Self {
+            kind,
+            flags: flags as u16,
+            payload: Vec::new(),
+        };
+        ret.push(as_bytes(header));
+        ret
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        self.payload.extend_from_slice(data);
+        self.payload.resize(align(self.payload.len()), 0);
+    }
+
+    /// Append attribute containing raw data.
+    pub fn attribute(&mut self, kind: c_ushort, data: &[u8]) -> &mut Self {
+        let header = nlattr {
+            nla_len: (size_of::<nlattr>() + data.len()) as u16,
+            nla_type: kind,
+        };
+        self.push(as_bytes(&header));
+        self.push(data);
+        self
+    }
+
+    /// Append nested attribute, containing all attributes appended by `build`.
+    pub fn nested<F: FnOnce(&mut Self)>(&mut self, kind: c_ushort, build: F) -> &mut Self {
+        let start = self.payload.len();
+        self.attribute(kind | NLA_F_NESTED as c_ushort, &[]);
+        build(self);
+        let len = (self.payload.len() - start) as u16;
+        self.payload[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+        self
+    }
+
+    fn serialize(&self, seq: u32) -> Vec<u8> {
+        let header = nlmsghdr {
+            nlmsg_len: (size_of::<nlmsghdr>() + self.payload.len()) as u32,
+            nlmsg_type: self.kind,
+            nlmsg_flags: self.flags,
+            nlmsg_seq: seq,
+            nlmsg_pid: 0,
+        };
+        let mut ret = as_bytes(&header).to_vec();
+        ret.extend_from_slice(&self.payload);
+        ret
+    }
+}
+
+/// Route netlink (`NETLINK_ROUTE`) socket, used to configure network interfaces.
+#[derive(Debug)]
+pub struct NetlinkSocket {
+    file: File,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+    /// Open new route netlink socket.
+    ///
+    /// # Errors
+    ///
+    /// If socket cannot be created, [CreationError::IoctlError](crate::error::CreationError) is returned.
+    pub fn open() -> Result<Self, CreationError> {
+        let fd = Errno::result(unsafe {
+            socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_ROUTE)
+        })?;
+        Ok(Self {
+            file: unsafe { File::from_raw_fd(fd) },
+            seq: 0,
+        })
+    }
+
+    /// Send request and wait until kernel acknowledges it.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses the request, [CreationError::IoctlError](crate::error::CreationError) is returned
+    /// containing the error code reported by kernel.
+    pub fn request(&mut self, mut message: NetlinkMessage) -> Result<(), CreationError> {
+        message.flags |= (NLM_F_REQUEST | NLM_F_ACK) as u16;
+        let seq = self.send(&message)?;
+        self.receive(seq, |_, _| ())
+    }
+
+    /// Send dump request, passing type and payload of each returned message to `handle`.
+    ///
+    /// # Errors
+    ///
+    /// If kernel refuses the request, [CreationError::IoctlError](crate::error::CreationError) is returned
+    /// containing the error code reported by kernel.
+    pub fn dump<F: FnMut(u16, &[u8])>(&mut self, mut message: NetlinkMessage, handle: F) -> Result<(), CreationError> {
+        message.flags |= (NLM_F_REQUEST | NLM_F_DUMP) as u16;
+        let seq = self.send(&message)?;
+        self.receive(seq, handle)
+    }
+
+    fn send(&mut self, message: &NetlinkMessage) -> Result<u32, CreationError> {
+        self.seq = self.seq.wrapping_add(1);
+        send(self.file.as_raw_fd(), &message.serialize(self.seq), MsgFlags::empty())?;
+        Ok(self.seq)
+    }
+
+    /// Receive responses to message with sequence number `seq`, passing type and payload of each
+    /// response to `handle`, until message is acknowledged or dump is finished.
+    fn receive<F: FnMut(u16, &[u8])>(&mut self, seq: u32, mut handle: F) -> Result<(), CreationError> {
+        let mut buf = vec![0u8; RECEIVE_BUFFER_SIZE];
+        loop {
+            let len = recv(self.file.as_raw_fd(), &mut buf, MsgFlags::empty())?;
+            for (header, payload) in messages(&buf[..len]) {
+                if header.nlmsg_seq != seq {
+                    continue;
+                }
+                match header.nlmsg_type as c_int {
+                    NLMSG_DONE => return Ok(()),
+                    NLMSG_ERROR => {
+                        let error: c_int = unsafe { ptr::read_unaligned(payload.as_ptr() as *const c_int) };
+                        return match error {
+                            0 => Ok(()),
+                            error => Err(nix::Error::Sys(Errno::from_i32(-error)).into()),
+                        };
+                    }
+                    _ => handle(header.nlmsg_type, payload),
+                }
+            }
+        }
+    }
+
+    /// Open route netlink socket subscribed to multicast `groups` (`RTMGRP_*`), which receives
+    /// notifications sent by kernel.
+    pub fn subscribe(groups: u32) -> Result<Self, CreationError> {
+        let socket = Self::open()?;
+        let mut address: sockaddr_nl = unsafe { std::mem::zeroed() };
+        address.nl_family = AF_NETLINK as sa_family_t;
+        address.nl_groups = groups;
+        Errno::result(unsafe {
+            bind(socket.file.as_raw_fd(), &address as *const _ as *const sockaddr, size_of::<sockaddr_nl>() as socklen_t)
+        })?;
+        Ok(socket)
+    }
+
+    /// Receive all pending notifications without waiting, passing type and payload of each to `handle`.
+    pub fn notifications<F: FnMut(u16, &[u8])>(&mut self, mut handle: F) -> Result<(), CreationError> {
+        let mut buf = vec![0u8; RECEIVE_BUFFER_SIZE];
+        loop {
+            let len = match recv(self.file.as_raw_fd(), &mut buf, MsgFlags::MSG_DONTWAIT) {
+                Err(nix::Error::Sys(Errno::EAGAIN)) => return Ok(()),
+                result => result?,
+            };
+            for (header, payload) in messages(&buf[..len]) {
+                handle(header.nlmsg_type, payload);
+            }
+        }
+    }
+}
+
+impl AsRawFd for NetlinkSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Split received datagram into headers and payloads of the messages it contains.
+fn messages(mut rest: &[u8]) -> impl Iterator<Item = (nlmsghdr, &[u8])> {
+    std::iter::from_fn(move || {
+        if rest.len() < size_of::<nlmsghdr>() {
+            return None;
+        }
+        let header: nlmsghdr = unsafe { ptr::read_unaligned(rest.as_ptr() as *const nlmsghdr) };
+        let len = header.nlmsg_len as usize;
+        if len < size_of::<nlmsghdr>() || len > rest.len() {
+            return None;
+        }
+        let payload = &rest[size_of::<nlmsghdr>()..len];
+        rest = &rest[align(len).min(rest.len())..];
+        Some((header, payload))
+    })
+}
+
+/// Split attributes following family specific header `H` in message payload into their types and data.
+pub fn attributes<H: Copy>(payload: &[u8]) -> impl Iterator<Item = (c_ushort, &[u8])> {
+    let mut rest = payload.get(align(size_of::<H>())..).unwrap_or(&[]);
+    std::iter::from_fn(move || {
+        if rest.len() < size_of::<nlattr>() {
+            return None;
+        }
+        let header: nlattr = unsafe { ptr::read_unaligned(rest.as_ptr() as *const nlattr) };
+        let len = header.nla_len as usize;
+        if len < size_of::<nlattr>() || len > rest.len() {
+            return None;
+        }
+        let data = &rest[size_of::<nlattr>()..len];
+        rest = &rest[align(len).min(rest.len())..];
+        Some((header.nla_type & NLA_TYPE_MASK as c_ushort, data))
+    })
+}
+
+/// Read family specific header `H` at the start of message payload.
+pub fn header<H: Copy>(payload: &[u8]) -> Option<H> {
+    if payload.len() < size_of::<H>() {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(payload.as_ptr() as *const H) })
+}
+
+/// Change attributes of existing interface with given index. Attributes are appended to the
+/// request by `build`.
+///
+/// # Arguments
+///
+/// * `kind` - Either `RTM_NEWLINK` or `RTM_SETLINK`.
+/// * `index` - Index of the changed interface.
+pub fn change_link<F: FnOnce(&mut NetlinkMessage)>(kind: u16, index: u32, build: F) -> Result<(), CreationError> {
+    let mut message = NetlinkMessage::new(kind, 0, &InterfaceInfoMessage::new(index));
+    build(&mut message);
+    NetlinkSocket::open()?.request(message)
+}
@@ -0,0 +1,150 @@
+use crate::unix::device::{DeviceMode, MAX_FRAME_SIZE};
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult};
+
+/// Magic numbers of classic pcap file, with microsecond and nanosecond timestamps.
+const MAGIC_MICROSECONDS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOSECONDS: u32 = 0xa1b2_3c4d;
+
+/// Link types of captured frames (`LINKTYPE_*`), ethernet frames and raw IP packets.
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+
+/// Reader of frames stored in classic pcap file (not pcapng), in either byte order.
+pub(crate) struct PcapReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    link_type: u32,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Read file header.
+    ///
+    /// # Errors
+    ///
+    /// If file is not classic pcap file, `InvalidData` error is returned.
+    pub fn new(mut reader: R) -> IoResult<Self> {
+        let mut header = [0u8; 24];
+        reader.read_exact(&mut header)?;
+        let magic = [header[0], header[1], header[2], header[3]];
+        let big_endian = match (u32::from_be_bytes(magic), u32::from_le_bytes(magic)) {
+            (MAGIC_MICROSECONDS, _) | (MAGIC_NANOSECONDS, _) => true,
+            (_, MAGIC_MICROSECONDS) | (_, MAGIC_NANOSECONDS) => false,
+            _ => return Err(IoError::new(ErrorKind::InvalidData, "file is not pcap capture")),
+        };
+        let mut ret = Self { reader, big_endian, link_type: 0 };
+        ret.link_type = ret.field(&header[20..24]);
+        Ok(ret)
+    }
+
+    /// Returns true, if captured frames can be written to device in given mode, i.e. they are
+    /// ethernet frames for tap or IP packets for tun device.
+    pub fn matches(&self, mode: DeviceMode) -> bool {
+        match mode {
+            DeviceMode::Tap => self.link_type == LINKTYPE_ETHERNET,
+            DeviceMode::Tun => [LINKTYPE_RAW, LINKTYPE_IPV4, LINKTYPE_IPV6].contains(&self.link_type),
+        }
+    }
+
+    /// Read next captured frame, `None` at the end of the file. Frames truncated by capture
+    /// (snap length) are returned as they were captured.
+    pub fn next_frame(&mut self) -> IoResult<Option<Vec<u8>>> {
+        let mut header = [0u8; 16];
+        let read = self.reader.read(&mut header)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        self.reader.read_exact(&mut header[read..])?;
+        let len = self.field(&header[8..12]) as usize;
+        if len > MAX_FRAME_SIZE {
+            return Err(IoError::new(ErrorKind::InvalidData, "captured frame is larger than 64 KiB"));
+        }
+        let mut frame = vec![0u8; len];
+        self.reader.read_exact(&mut frame)?;
+        Ok(Some(frame))
+    }
+
+    fn field(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Classic pcap file with given magic and link type, containing `frames`, in given byte order.
+    fn capture(magic: u32, link_type: u32, frames: &[&[u8]], big_endian: bool) -> Vec<u8> {
+        let field = |value: u32| if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+        let mut file = Vec::new();
+        file.extend_from_slice(&field(magic));
+        // Version 2.4, timezone, accuracy and snap length
+        file.extend_from_slice(if big_endian { &[0, 2, 0, 4] } else { &[2, 0, 4, 0] });
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&field(65535));
+        file.extend_from_slice(&field(link_type));
+        for (i, frame) in frames.iter().enumerate() {
+            file.extend_from_slice(&field(i as u32));
+            file.extend_from_slice(&field(0));
+            file.extend_from_slice(&field(frame.len() as u32));
+            file.extend_from_slice(&field(frame.len() as u32 + 4));
+            file.extend_from_slice(frame);
+        }
+        file
+    }
+
+    #[test]
+    fn read_both_byte_orders() {
+        let frames: &[&[u8]] = &[&[1, 2, 3], &[], &[4; 60]];
+        for &big_endian in &[false, true] {
+            for &magic in &[MAGIC_MICROSECONDS, MAGIC_NANOSECONDS] {
+                let file = capture(magic, LINKTYPE_ETHERNET, frames, big_endian);
+                let mut reader = PcapReader::new(&file[..]).unwrap();
+                assert!(reader.matches(DeviceMode::Tap));
+                assert!(!reader.matches(DeviceMode::Tun));
+                for frame in frames {
+                    assert_eq!(reader.next_frame().unwrap().as_deref(), Some(*frame));
+                }
+                assert!(reader.next_frame().unwrap().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn link_types() {
+        for &link_type in &[LINKTYPE_RAW, LINKTYPE_IPV4, LINKTYPE_IPV6] {
+            let file = capture(MAGIC_MICROSECONDS, link_type, &[], false);
+            let reader = PcapReader::new(&file[..]).unwrap();
+            assert!(reader.matches(DeviceMode::Tun));
+            assert!(!reader.matches(DeviceMode::Tap));
+        }
+        let file = capture(MAGIC_MICROSECONDS, 113, &[], true);
+        let reader = PcapReader::new(&file[..]).unwrap();
+        assert!(!reader.matches(DeviceMode::Tun) && !reader.matches(DeviceMode::Tap));
+    }
+
+    #[test]
+    fn invalid_header() {
+        // pcapng section header block
+        let mut file = capture(MAGIC_MICROSECONDS, LINKTYPE_ETHERNET, &[], false);
+        file[..4].copy_from_slice(&[0x0a, 0x0d, 0x0d, 0x0a]);
+        assert_eq!(PcapReader::new(&file[..]).err().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(PcapReader::new(&file[..20]).err().unwrap().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let file = capture(MAGIC_MICROSECONDS, LINKTYPE_ETHERNET, &[&[0; 100]], false);
+        let mut reader = PcapReader::new(&file[..file.len() - 1]).unwrap();
+        assert_eq!(reader.next_frame().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        let mut reader = PcapReader::new(&file[..30]).unwrap();
+        assert_eq!(reader.next_frame().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let mut file = capture(MAGIC_MICROSECONDS, LINKTYPE_ETHERNET, &[&[0; 100]], false);
+        file[32..36].copy_from_slice(&(MAX_FRAME_SIZE as u32 + 1).to_le_bytes());
+        let mut reader = PcapReader::new(&file[..]).unwrap();
+        assert_eq!(reader.next_frame().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::unix::utils::read_sysfs;
+use std::{
+    io::{Error as IoError, ErrorKind, Result as IoResult},
+    thread,
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Number of frames dropped by the device between two samples of [DropMonitor].
+pub struct DropDelta {
+    /// Frames dropped while being received (written to the device).
+    pub rx_dropped: u64,
+    /// Frames dropped while being transmitted (e.g. because queue of frames waiting for read is full).
+    pub tx_dropped: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Result of throughput measurement, see [Device::bench_write](crate::unix::Device::bench_write)
+/// and [Device::bench_read](crate::unix::Device::bench_read).
+pub struct BenchResult {
+    /// Number of frames written or read.
+    pub frames: u64,
+    /// Number of bytes written or read, including packet info and virtio header.
+    pub bytes: u64,
+    /// Number of writes, which failed with `WouldBlock` in non-blocking mode (device queue was full).
+    pub would_block: u64,
+    /// Duration of the measurement.
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Returns number of frames per second.
+    pub fn frames_per_sec(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    /// Returns number of bytes per second.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Sysfs state of single queue of the device, see [Device::queue_stats](crate::unix::Device::queue_stats).
+/// These are not traffic counters: kernel does not expose frames or bytes per queue of tun/tap device, use
+/// [QueueCounters] for them. Values, which are missing in sysfs (e.g. queue has no transmit side, or driver
+/// does not use byte queue limits), are `None`.
+pub struct QueueStats {
+    /// Index of the queue (`rx-<index>` and `tx-<index>`).
+    pub index: usize,
+    /// Number of transmit timeouts of the queue (`tx_timeout`).
+    pub tx_timeouts: Option<u64>,
+    /// Bytes queued for transmission, but not completed yet (`byte_queue_limits/inflight`).
+    pub tx_inflight: Option<u64>,
+    /// Current limit of bytes in flight (`byte_queue_limits/limit`).
+    pub tx_limit: Option<u64>,
+    /// Size of flow table of receive packet steering (`rps_flow_cnt`).
+    pub rx_flow_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// Frames read from and written to single file descriptor (i.e. single queue of multi-queue device), counted
+/// by the crate, see [Device::queue_counters](crate::unix::Device::queue_counters). Bytes include packet info
+/// and virtio header. Only traffic of this descriptor is counted, not traffic of other descriptors or
+/// processes using the same queue.
+pub struct QueueCounters {
+    /// Frames read from the queue.
+    pub read_frames: u64,
+    /// Bytes read from the queue.
+    pub read_bytes: u64,
+    /// Frames written to the queue.
+    pub written_frames: u64,
+    /// Bytes written to the queue.
+    pub written_bytes: u64,
+}
+
+impl QueueCounters {
+    pub(crate) fn count_read(&mut self, len: usize) {
+        self.read_frames += 1;
+        self.read_bytes += len as u64;
+    }
+
+    pub(crate) fn count_written(&mut self, len: usize) {
+        self.written_frames += 1;
+        self.written_bytes += len as u64;
+    }
+}
+
+/// Read counters of all queues of the device from sysfs (`/sys/class/net/<name>/queues`).
+pub(crate) fn queue_stats(name: &str) -> IoResult<Vec<QueueStats>> {
+    read_sysfs(name, "ifindex")?;
+    let mut count = 0;
+    for entry in std::fs::read_dir(format!("/sys/class/net/{}/queues", name))? {
+        let entry = entry?.file_name();
+        let index = entry.to_str()
+            .and_then(|queue| queue.strip_prefix("rx-").or_else(|| queue.strip_prefix("tx-")))
+            .and_then(|index| index.parse::<usize>().ok());
+        if let Some(index) = index {
+            count = count.max(index + 1);
+        }
+    }
+    let counter = |queue: String| -> IoResult<Option<u64>> {
+        match read_sysfs(name, &format!("queues/{}", queue)) {
+            Ok(value) => value.parse().map(Some).map_err(|e| IoError::new(ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    };
+    (0..count)
+        .map(|index| {
+            Ok(QueueStats {
+                index,
+                tx_timeouts: counter(format!("tx-{}/tx_timeout", index))?,
+                tx_inflight: counter(format!("tx-{}/byte_queue_limits/inflight", index))?,
+                tx_limit: counter(format!("tx-{}/byte_queue_limits/limit", index))?,
+                rx_flow_count: counter(format!("rx-{}/rps_flow_cnt", index))?,
+            })
+        })
+        .collect()
+}
+
+/// Sampler of drop counters (`rx_dropped` and `tx_dropped`) of the device, created with
+/// [Device::drop_monitor](crate::unix::Device::drop_monitor).
+///
+/// # Remarks
+///
+/// Counters are read from sysfs (`/sys/class/net/<name>/statistics`). As iterator, it blocks for
+/// `interval` and yields counter increments since the previous sample, the first sample is taken
+/// by the first call of `next`. Iterator ends only when counters cannot be read (e.g. device was removed),
+/// after yielding the error.
+#[derive(Debug, Clone)]
+pub struct DropMonitor {
+    name: String,
+    interval: Duration,
+    last: Option<(u64, u64)>,
+    failed: bool,
+}
+
+impl DropMonitor {
+    pub(crate) fn new(name: &str, interval: Duration) -> Self {
+        Self {
+            name: name.to_string(),
+            interval,
+            last: None,
+            failed: false,
+        }
+    }
+
+    /// Returns sampling interval.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn read_counter(&self, counter: &str) -> IoResult<u64> {
+        read_sysfs(&self.name, &format!("statistics/{}", counter))?.parse()
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Read counters and return their increments since the previous sample, or zeros if it is the first sample.
+    pub fn sample(&mut self) -> IoResult<DropDelta> {
+        let current = (self.read_counter("rx_dropped")?, self.read_counter("tx_dropped")?);
+        let delta = match self.last {
+            Some(last) => DropDelta {
+                rx_dropped: counter_delta(last.0, current.0),
+                tx_dropped: counter_delta(last.1, current.1),
+            },
+            None => DropDelta::default(),
+        };
+        self.last = Some(current);
+        Ok(delta)
+    }
+}
+
+/// Difference of two counter values, accounting for wraparound of both 32 bit and 64 bit counters.
+fn counter_delta(last: u64, current: u64) -> u64 {
+    if current >= last {
+        current - last
+    } else if last <= u32::MAX as u64 {
+        (u32::MAX as u64 - last) + current + 1
+    } else {
+        current.wrapping_sub(last)
+    }
+}
+
+impl Iterator for DropMonitor {
+    type Item = IoResult<DropDelta>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        if self.last.is_none() {
+            if let Err(e) = self.sample() {
+                self.failed = true;
+                return Some(Err(e));
+            }
+        }
+        thread::sleep(self.interval);
+        let result = self.sample();
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_without_wraparound() {
+        assert_eq!(counter_delta(10, 10), 0);
+        assert_eq!(counter_delta(10, 25), 15);
+        assert_eq!(counter_delta(u32::MAX as u64, u32::MAX as u64 + 5), 5);
+    }
+
+    #[test]
+    fn delta_32_bit_wraparound() {
+        assert_eq!(counter_delta(u32::MAX as u64 - 2, 4), 7);
+        assert_eq!(counter_delta(u32::MAX as u64, 0), 1);
+    }
+
+    #[test]
+    fn delta_64_bit_wraparound() {
+        assert_eq!(counter_delta(u64::MAX - 1, 3), 5);
+        assert_eq!(counter_delta(u32::MAX as u64 + 1, 0), u64::MAX - u32::MAX as u64);
+    }
+
+    #[test]
+    fn loopback_drop_monitor() {
+        let mut monitor = DropMonitor::new("lo", Duration::from_millis(1));
+        assert_eq!(monitor.sample().unwrap(), DropDelta::default());
+        assert!(monitor.next().unwrap().is_ok());
+        let mut gone = DropMonitor::new("tippytap-none", Duration::from_millis(1));
+        assert!(gone.next().unwrap().is_err());
+        assert!(gone.next().is_none());
+    }
+
+    #[test]
+    #[ignore = "requires CAP_NET_ADMIN"]
+    fn forced_tx_drops() {
+        use crate::unix::{DeviceBuilder, DeviceMode};
+        use std::net::{Ipv4Addr, UdpSocket};
+
+        // Frames sent by kernel are dropped once transmit queue of 2 frames is full and nobody reads them
+        let mut device = DeviceBuilder::new(DeviceMode::Tun).txqueuelen(2).open().unwrap();
+        device.setup_ipv4(Ipv4Addr::new(10, 92, 31, 1), 24, true).unwrap();
+        let mut monitor = device.drop_monitor(Duration::from_millis(10));
+        monitor.sample().unwrap();
+        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        for _ in 0..64 {
+            socket.send_to(b"burst", (Ipv4Addr::new(10, 92, 31, 2), 9)).unwrap();
+        }
+        let delta = monitor.next().unwrap().unwrap();
+        assert!(delta.tx_dropped > 0);
+    }
+
+    #[test]
+    fn bench_rates() {
+        let result = BenchResult {
+            frames: 500,
+            bytes: 750_000,
+            elapsed: Duration::from_millis(250),
+            ..BenchResult::default()
+        };
+        assert_eq!(result.frames_per_sec(), 2000.0);
+        assert_eq!(result.bytes_per_sec(), 3_000_000.0);
+    }
+
+    #[test]
+    fn loopback_queues() {
+        let queues = queue_stats("lo").unwrap();
+        assert!(!queues.is_empty());
+        assert!(queues.iter().enumerate().all(|(i, queue)| queue.index == i));
+        assert_eq!(queue_stats("tippytap-none").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn queue_counters() {
+        let mut counters = QueueCounters::default();
+        counters.count_read(60);
+        counters.count_read(1514);
+        counters.count_written(42);
+        assert_eq!(counters, QueueCounters { read_frames: 2, read_bytes: 1574, written_frames: 1, written_bytes: 42 });
+    }
+}
@@ -2,12 +2,35 @@ use libc::*;
 use crate::{
     error::*,
 };
+use nix::errno::Errno;
 use std::{
+    convert::TryInto,
+    ffi::{CString, OsStr},
     io::ErrorKind,
-    os::unix::io::AsRawFd,
+    net::Ipv4Addr,
+    os::unix::{
+        ffi::OsStrExt,
+        io::{AsRawFd, FromRawFd, RawFd},
+        net::UnixStream,
+    },
     fs::{OpenOptions, File},
 };
 
+/// How many times is interrupted (`EINTR`) ioctl call retried, before error is returned.
+const EINTR_RETRIES: usize = 8;
+
+/// Capability required to create and configure network devices.
+const CAP_NET_ADMIN: u32 = 12;
+
+/// Check whether calling process has `CAP_NET_ADMIN` capability in its effective set.
+/// If it cannot be determined (e.g. `/proc` is not mounted), `None` is returned.
+pub(crate) fn has_net_admin() -> Option<bool> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let caps = status.lines().find_map(|line| line.strip_prefix("CapEff:"))?;
+    let caps = u64::from_str_radix(caps.trim(), 16).ok()?;
+    Some(caps & (1 << CAP_NET_ADMIN) != 0)
+}
+
 /// Returns a file descriptor to `/dev/net/tun`.
 pub(crate) fn get_fd() -> Result<File, CreationError> {
     OpenOptions::new()
@@ -23,6 +46,209 @@ pub(crate) fn get_fd() -> Result<File, CreationError> {
         })
 }
 
+/// Returns index of network interface with given name.
+///
+/// # Errors
+///
+/// If name contains nul, [CreationError::InvalidName](crate::error::CreationError) is returned.
+/// If there is no such interface, [CreationError::DeviceGone](crate::error::CreationError) is returned.
+pub(crate) fn interface_index(name: &str) -> Result<u32, CreationError> {
+    let name = CString::new(name)
+        .map_err(|e| StringError::UnexpectedNull(e.nul_position()))?;
+    match unsafe { if_nametoindex(name.as_ptr()) } {
+        0 => Err(nix::Error::last().into()),
+        index => Ok(index),
+    }
+}
+
+/// Returns name of network interface with given index, or `None` if there is no such interface.
+pub(crate) fn interface_name(index: u32) -> Option<String> {
+    let mut name = [0 as c_char; IF_NAMESIZE];
+    let ptr = unsafe { if_indextoname(index, name.as_mut_ptr()) };
+    if ptr.is_null() {
+        return None;
+    }
+    unsafe { std::ffi::CStr::from_ptr(ptr) }.to_str().ok().map(String::from)
+}
+
+/// Read attribute of network device from sysfs (`/sys/class/net/<name>/<attribute>`), with
+/// surrounding whitespace trimmed.
+///
+/// # Errors
+///
+/// If the device itself does not exist, `NotFound` error containing
+/// [CreationError::DeviceGone](crate::error::CreationError) is returned.
+pub(crate) fn read_sysfs(device_name: &str, attribute: &str) -> std::io::Result<String> {
+    let device = format!("/sys/class/net/{}", device_name);
+    match std::fs::read_to_string(format!("{}/{}", device, attribute)) {
+        Ok(value) => Ok(value.trim().to_string()),
+        Err(e) if e.kind() == ErrorKind::NotFound && !std::path::Path::new(&device).exists() => {
+            Err(std::io::Error::new(ErrorKind::NotFound, CreationError::DeviceGone))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns multicast groups joined by userspace (`SIOCADDMULTI`), read from `/proc/net/dev_mcast`.
+/// Groups joined by kernel itself (e.g. by IPv6 stack) are not included.
+pub(crate) fn multicast_groups(device_name: &str) -> std::io::Result<Vec<[u8; 6]>> {
+    let table = std::fs::read_to_string("/proc/net/dev_mcast")?;
+    let mut groups = Vec::new();
+    for line in table.lines() {
+        // index, name, users, global users, address
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 5 || fields[1] != device_name || fields[3] == "0" || fields[4].len() != 12 {
+            continue;
+        }
+        let mut mac = [0u8; 6];
+        for (i, byte) in mac.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&fields[4][2 * i..2 * i + 2], 16)
+                .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+        }
+        groups.push(mac);
+    }
+    Ok(groups)
+}
+
+/// Size of bridge forwarding database entry (`struct __fdb_entry`) in `brforward` sysfs file.
+const FDB_ENTRY_SIZE: usize = 16;
+
+/// Returns true, if device is a bridge port, and its bridge learned `mac` on other port than its own
+/// (i.e. address is used by other host on the bridged segment). For devices, which are not bridge
+/// ports, false is returned.
+pub(crate) fn bridge_learned(device_name: &str, mac: [u8; 6]) -> std::io::Result<bool> {
+    let port = format!("/sys/class/net/{}/brport", device_name);
+    if !std::path::Path::new(&port).exists() {
+        return Ok(false);
+    }
+    let entries = std::fs::read(format!("/sys/class/net/{}/master/brforward", device_name))?;
+    // mac_addr[6], port_no, is_local, ageing_timer_value, port_hi, pad0, unused
+    Ok(entries.chunks_exact(FDB_ENTRY_SIZE).any(|entry| entry[..6] == mac && entry[7] == 0))
+}
+
+/// Returns a datagram socket, which is used as a handle for network device ioctl calls (`SIOC*`).
+pub(crate) fn get_control_socket() -> Result<File, CreationError> {
+    let fd = Errno::result(unsafe { socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0) })?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Switch file descriptor to non-blocking (or back to blocking) mode.
+pub(crate) fn set_nonblocking(file: &File, nonblocking: bool) -> std::io::Result<()> {
+    let fd = file.as_raw_fd();
+    let flags = unsafe { fcntl(fd, F_GETFL) };
+    if flags < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let flags = if nonblocking { flags | O_NONBLOCK } else { flags & !O_NONBLOCK };
+    if unsafe { fcntl(fd, F_SETFL, flags) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns true, if close-on-exec flag (`FD_CLOEXEC`) of file descriptor is set.
+pub(crate) fn get_cloexec(file: &File) -> std::io::Result<bool> {
+    let flags = unsafe { fcntl(file.as_raw_fd(), F_GETFD) };
+    if flags < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(flags & FD_CLOEXEC != 0)
+}
+
+/// Set or clear close-on-exec flag (`FD_CLOEXEC`) of file descriptor.
+pub(crate) fn set_cloexec(file: &File, cloexec: bool) -> std::io::Result<()> {
+    let fd = file.as_raw_fd();
+    let flags = unsafe { fcntl(fd, F_GETFD) };
+    if flags < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let flags = if cloexec { flags | FD_CLOEXEC } else { flags & !FD_CLOEXEC };
+    if unsafe { fcntl(fd, F_SETFD, flags) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Send file descriptor over unix socket as ancillary data (`SCM_RIGHTS`), along with single byte.
+pub(crate) fn send_fd(socket: &UnixStream, fd: RawFd) -> std::io::Result<()> {
+    let mut byte = [0u8];
+    let mut iov = iovec { iov_base: byte.as_mut_ptr() as *mut c_void, iov_len: 1 };
+    let mut control = vec![0u8; unsafe { CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) } as usize];
+    let mut message: msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut c_void;
+    message.msg_controllen = control.len();
+    unsafe {
+        let header = CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = SOL_SOCKET;
+        (*header).cmsg_type = SCM_RIGHTS;
+        (*header).cmsg_len = CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as usize;
+        std::ptr::write_unaligned(CMSG_DATA(header) as *mut RawFd, fd);
+    }
+    if unsafe { sendmsg(socket.as_raw_fd(), &message, MSG_NOSIGNAL) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receive file descriptor sent by [send_fd]. Received descriptor has close-on-exec flag set.
+pub(crate) fn recv_fd(socket: &UnixStream) -> std::io::Result<File> {
+    let mut byte = [0u8];
+    let mut iov = iovec { iov_base: byte.as_mut_ptr() as *mut c_void, iov_len: 1 };
+    let mut control = vec![0u8; unsafe { CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) } as usize];
+    let mut message: msghdr = unsafe { std::mem::zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut c_void;
+    message.msg_controllen = control.len();
+    let read = unsafe { recvmsg(socket.as_raw_fd(), &mut message, MSG_CMSG_CLOEXEC) };
+    if read < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let header = unsafe { CMSG_FIRSTHDR(&message) };
+    if header.is_null() || unsafe { (*header).cmsg_level != SOL_SOCKET || (*header).cmsg_type != SCM_RIGHTS } {
+        let kind = if read == 0 { ErrorKind::UnexpectedEof } else { ErrorKind::InvalidData };
+        return Err(std::io::Error::new(kind, "message does not carry file descriptor"));
+    }
+    // Descriptors truncated by MSG_CTRUNC are not installed, so at most one descriptor is received
+    let fd = unsafe { std::ptr::read_unaligned(CMSG_DATA(header) as *const RawFd) };
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Returns a packet socket, which does not receive any frames, used to hold device memberships
+/// (promiscuous and all-multicast mode), which are released when the socket is closed.
+pub(crate) fn get_membership_socket() -> Result<File, CreationError> {
+    let fd = Errno::result(unsafe { socket(AF_PACKET, SOCK_RAW | SOCK_CLOEXEC, 0) })?;
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Add or drop membership (`PACKET_MR_PROMISC` or `PACKET_MR_ALLMULTI`) of the device, held by packet socket.
+///
+/// # Remarks
+///
+/// Kernel counts memberships, so each added membership must be dropped to leave the mode.
+pub(crate) fn set_membership(socket: &File, index: u32, kind: c_int, add: bool) -> Result<(), CreationError> {
+    let request = packet_mreq {
+        mr_ifindex: index as c_int,
+        mr_type: kind as c_ushort,
+        mr_alen: 0,
+        mr_address: [0; 8],
+    };
+    let option = if add { PACKET_ADD_MEMBERSHIP } else { PACKET_DROP_MEMBERSHIP };
+    Errno::result(unsafe {
+        setsockopt(socket.as_raw_fd(), SOL_PACKET, option,
+                   &request as *const _ as *const c_void, std::mem::size_of::<packet_mreq>() as socklen_t)
+    })?;
+    Ok(())
+}
+
+/// Fill `buf` with random bytes from kernel random number generator.
+pub(crate) fn random_bytes(buf: &mut [u8]) -> Result<(), CreationError> {
+    Errno::result(unsafe { getrandom(buf.as_mut_ptr() as *mut c_void, buf.len(), 0) })?;
+    Ok(())
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 /// Structure representing name of specific network device.
@@ -51,16 +277,16 @@ impl InterfaceName {
     pub fn from_str<S: AsRef<str>>(name: S) -> Result<Self, StringError> {
         use StringError::*;
         let name = name.as_ref();
-        if name.len() == 0 {
+        if name.is_empty() {
             return Ok(Self::empty());
         }
         // 1. check that str is ascii only and it does not contains nul terminator inside
-        if let Some(pos) = name.chars().position(|x| !x.is_ascii() || (x as u8) != 0) {
+        if let Some(pos) = name.chars().position(|x| !x.is_ascii() || (x as u8) == 0) {
             return Err(InvalidCharacter(pos));
         }
         // 2. Check if it is not too long.
         if name.len() >= IFNAMSIZ {
-            return Err(StringTooLong(IFNAMSIZ));
+            return Err(StringTooLong { max: IFNAMSIZ - 1, len: name.len() });
         }
         let mut buf = [0 as c_char; IFNAMSIZ];
 
@@ -73,17 +299,47 @@ impl InterfaceName {
         })
     }
 
+    /// Try to create InterfaceName from OS string, e.g. file name of `/sys/class/net/<name>`.
+    ///
+    /// # Args
+    /// * `name` - A name of device
+    ///
+    /// # Errors
+    /// Same as [InterfaceName::from_str], for first non-ASCII byte (OS string does not have to be valid UTF-8)
+    /// `InvalidCharacter` with its position is returned.
+    pub fn from_os_str(name: &OsStr) -> Result<Self, StringError> {
+        let bytes = name.as_bytes();
+        if let Some(pos) = bytes.iter().position(|byte| !byte.is_ascii()) {
+            return Err(StringError::InvalidCharacter(pos));
+        }
+        // All bytes are ASCII, so name is valid UTF-8
+        Self::from_str(name.to_str().unwrap_or_default())
+    }
+
+    /// Length of the name, without the nul terminator.
+    ///
+    /// # Remarks
+    ///
+    /// Kernel always treats last byte of the buffer as nul terminator, so even if buffer
+    /// is not terminated, length is at most `IFNAMSIZ - 1`.
+    pub fn len(&self) -> usize {
+        self.name[..IFNAMSIZ - 1].iter().position(|x| *x == 0)
+            .unwrap_or(IFNAMSIZ - 1)
+    }
+
+    /// Returns true, if name is empty (kernel will assign name to the device).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Try to represent InterfaceName as Rust String.
     ///
     /// # Errors
     ///
-    /// If nul terminator is not present in name [StringError::MangledString] is returned.
     /// If name contains non-ascii character [StringError::InvalidCharacter] is returned.
-    pub fn to_string(&self) -> Result<String, StringError> {
+    pub fn to_string(self) -> Result<String, StringError> {
         use StringError::*;
-        let end = self.name.iter().position(|x| *x == 0)
-            .ok_or(MangledString)?;
-        let slice: &[i8] = &self.name[..end];
+        let slice: &[c_char] = &self.name[..self.len()];
         let mut ret = String::with_capacity(slice.len());
         for (pos, byte) in slice.iter().enumerate() {
             let chr = *byte as u8 as char;
@@ -122,14 +378,45 @@ pub union InterfaceFieldReplaceUnit {
     if_index: c_int,
     metric: c_int,
     mtu: c_int,
+    tx_queue_len: c_int,
     map: InterfaceMap,
     slave: InterfaceName,
     new_name: InterfaceName,
     data: *mut c_void,
 }
 
+/// Convert IPv4 address into generic socket address.
+fn inet_to_sockaddr(address: Ipv4Addr) -> sockaddr {
+    let inet = sockaddr_in {
+        sin_family: AF_INET as sa_family_t,
+        sin_port: 0,
+        sin_addr: in_addr {
+            s_addr: u32::from(address).to_be(),
+        },
+        sin_zero: [0; 8],
+    };
+    unsafe {
+        std::mem::transmute::<sockaddr_in, sockaddr>(inet)
+    }
+}
+
+/// Convert generic socket address into IPv4 address, if it is one.
+fn sockaddr_to_inet(address: &sockaddr) -> Option<Ipv4Addr> {
+    if address.sa_family != AF_INET as sa_family_t {
+        return None;
+    }
+    let inet = unsafe {
+        std::mem::transmute::<sockaddr, sockaddr_in>(*address)
+    };
+    Some(Ipv4Addr::from(u32::from_be(inet.sin_addr.s_addr)))
+}
+
+/// Union members are plain C types, for which any bit pattern is valid, so reading any of them is
+/// not undefined behaviour. Readers (`get_*`) are still meaningful only if the request was made for
+/// the same field (e.g. `get_mtu` after `SIOCGIFMTU`), otherwise they return garbage.
 impl InterfaceFieldReplaceUnit {
-    fn new() -> Self {
+    /// Create zeroed IFFRU, used for requests querying the device
+    pub fn new() -> Self {
         unsafe {
             std::mem::zeroed()
         }
@@ -141,6 +428,155 @@ impl InterfaceFieldReplaceUnit {
         ret.flags = flags.into();
         ret
     }
+
+    /// Create IFFRU to replace IPv4 address
+    pub fn address(address: Ipv4Addr) -> Self {
+        let mut ret = Self::new();
+        ret.address = inet_to_sockaddr(address);
+        ret
+    }
+
+    /// Create IFFRU to replace IPv4 netmask
+    pub fn netmask(netmask: Ipv4Addr) -> Self {
+        let mut ret = Self::new();
+        ret.netmask = inet_to_sockaddr(netmask);
+        ret
+    }
+
+    /// Create IFFRU to replace IPv4 broadcast address
+    pub fn broadcast_address(address: Ipv4Addr) -> Self {
+        let mut ret = Self::new();
+        ret.broadcast_address = inet_to_sockaddr(address);
+        ret
+    }
+
+    /// Create IFFRU to replace IPv4 destination (peer) address of point-to-point device
+    pub fn destination_address(address: Ipv4Addr) -> Self {
+        let mut ret = Self::new();
+        ret.destination_address = inet_to_sockaddr(address);
+        ret
+    }
+
+    /// Create IFFRU to replace ethernet hardware (MAC) address
+    pub fn hw_address(mac: [u8; 6]) -> Self {
+        let mut address: sockaddr = unsafe { std::mem::zeroed() };
+        address.sa_family = ARPHRD_ETHER;
+        for (dst, src) in address.sa_data.iter_mut().zip(mac.iter()) {
+            *dst = *src as c_char;
+        }
+        let mut ret = Self::new();
+        ret.hw_address = address;
+        ret
+    }
+
+    /// Create IFFRU with link layer multicast address, used to join or leave multicast group
+    pub fn multicast_address(mac: [u8; 6]) -> Self {
+        let mut ret = Self::hw_address(mac);
+        ret.hw_address.sa_family = AF_UNSPEC as sa_family_t;
+        ret
+    }
+
+    /// Create IFFRU to replace maximal transmission unit
+    pub fn mtu(mtu: c_int) -> Self {
+        let mut ret = Self::new();
+        ret.mtu = mtu;
+        ret
+    }
+
+    /// Create IFFRU to replace length of transmit queue
+    pub fn tx_queue_len(tx_queue_len: c_int) -> Self {
+        let mut ret = Self::new();
+        ret.tx_queue_len = tx_queue_len;
+        ret
+    }
+
+    /// Create IFFRU pointing to request specific data (e.g. ethtool command)
+    pub fn data(data: *mut c_void) -> Self {
+        let mut ret = Self::new();
+        ret.data = data;
+        ret
+    }
+
+    /// Create IFFRU to replace interface index
+    pub fn if_index(if_index: c_int) -> Self {
+        let mut ret = Self::new();
+        ret.if_index = if_index;
+        ret
+    }
+
+    /// Create IFFRU to replace interface metric
+    pub fn metric(metric: c_int) -> Self {
+        let mut ret = Self::new();
+        ret.metric = metric;
+        ret
+    }
+
+    /// Create IFFRU to replace interface memory mapping
+    pub fn map(map: InterfaceMap) -> Self {
+        let mut ret = Self::new();
+        ret.map = map;
+        ret
+    }
+
+    /// Read flags
+    pub fn get_flags(&self) -> c_short {
+        unsafe { self.flags }
+    }
+
+    /// Read IPv4 address, `None` if address is not IPv4 address
+    pub fn get_address(&self) -> Option<Ipv4Addr> {
+        sockaddr_to_inet(unsafe { &self.address })
+    }
+
+    /// Read IPv4 netmask, `None` if netmask is not IPv4 address
+    pub fn get_netmask(&self) -> Option<Ipv4Addr> {
+        sockaddr_to_inet(unsafe { &self.netmask })
+    }
+
+    /// Read IPv4 broadcast address, `None` if address is not IPv4 address
+    pub fn get_broadcast_address(&self) -> Option<Ipv4Addr> {
+        sockaddr_to_inet(unsafe { &self.broadcast_address })
+    }
+
+    /// Read IPv4 destination address, `None` if address is not IPv4 address
+    pub fn get_destination_address(&self) -> Option<Ipv4Addr> {
+        sockaddr_to_inet(unsafe { &self.destination_address })
+    }
+
+    /// Read ethernet hardware (MAC) address
+    pub fn get_hw_address(&self) -> [u8; 6] {
+        let data = unsafe { self.hw_address.sa_data };
+        let mut mac = [0u8; 6];
+        for (dst, src) in mac.iter_mut().zip(data.iter()) {
+            *dst = *src as u8;
+        }
+        mac
+    }
+
+    /// Read maximal transmission unit
+    pub fn get_mtu(&self) -> c_int {
+        unsafe { self.mtu }
+    }
+
+    /// Read length of transmit queue
+    pub fn get_tx_queue_len(&self) -> c_int {
+        unsafe { self.tx_queue_len }
+    }
+
+    /// Read interface index
+    pub fn get_if_index(&self) -> c_int {
+        unsafe { self.if_index }
+    }
+
+    /// Read interface metric
+    pub fn get_metric(&self) -> c_int {
+        unsafe { self.metric }
+    }
+
+    /// Read interface memory mapping
+    pub fn get_map(&self) -> InterfaceMap {
+        unsafe { self.map }
+    }
 }
 
 #[repr(C)]
@@ -158,6 +594,29 @@ impl InterfaceRequest {
         &self.name
     }
 
+    /// Get field replace unit of this request, containing the result of query requests
+    pub fn get_fru(&self) -> &InterfaceFieldReplaceUnit {
+        &self.fru
+    }
+
+    /// Create new request for network device ioctl calls (`SIOC*`).
+    ///
+    /// # Arguments
+    ///
+    /// * `device_name` - Name of the device, the request is about.
+    /// * `fru` - Field which is replaced or queried by the request.
+    ///
+    /// # Errors
+    ///
+    /// If `device_name` is invalid ASCII string or is longer than `IFNAMSIZ` error is return describing
+    /// whats wrong with the name.
+    pub fn new<S: AsRef<str>>(device_name: S, fru: InterfaceFieldReplaceUnit) -> Result<Self, StringError> {
+        Ok(Self {
+            name: InterfaceName::from_str(device_name)?,
+            fru,
+        })
+    }
+
     /// Create new request to upgrade a file descriptor to tun/tap device.
     ///
     /// # Arguments
@@ -177,7 +636,8 @@ impl InterfaceRequest {
     }
 }
 
-/// Upgrade file descriptor to bind to a device described in the InterfaceRequest.
+/// Upgrade file descriptor to bind to a device described in the InterfaceRequest, and return
+/// name assigned to the device.
 ///
 /// # Arguments
 ///
@@ -186,6 +646,8 @@ impl InterfaceRequest {
 ///
 /// # Returns
 ///
+/// Name of the device, as assigned by the kernel. If request was made with empty name or with
+/// pattern (e.g. `tap%d`), kernel fills the name in the request, otherwise requested name is returned.
 /// If anything is wrong with the upgrade, [CreationError::IoctlError](crate::error::CreationError) is returned
 /// containing an Linux error-code.
 ///
@@ -196,19 +658,522 @@ impl InterfaceRequest {
 /// It is required, that given file has a filed descriptor for `/dev/net/tun` and request was made with [InterfaceRequest::tun_set_request](self::InterfaceRequest::tun_set_request),
 /// otherwise it is not guaranteed to work and error code is more or less orientational as it is just wraps
 /// linux `errno()`.
-pub fn tun_set_interface(file: &File, request: &mut InterfaceRequest) -> Result<(), CreationError> {
+/// If call is interrupted by signal (`EINTR`), it is retried up to `EINTR_RETRIES` times.
+pub fn tun_set_interface(file: &File, request: &mut InterfaceRequest) -> Result<String, CreationError> {
     let fd = file.as_raw_fd();
     let ptr = request as *const _ as u64;
+    let mut retries = 0;
+    loop {
+        match unsafe { ioctl::tunsetiff(fd, ptr) } {
+            Err(nix::Error::Sys(Errno::EINTR)) if retries < EINTR_RETRIES => retries += 1,
+            result => {
+                result?;
+                break;
+            }
+        }
+    }
+    Ok(request.get_name().to_string()?)
+}
+
+/// Get name and flags of the device attached to the file (`TUNGETIFF`).
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+pub fn tun_get_interface(file: &File) -> Result<InterfaceRequest, CreationError> {
+    let mut request = InterfaceRequest::new("", InterfaceFieldReplaceUnit::new())?;
     unsafe {
-        ioctl::tunsetiff(fd, ptr)?;
+        ioctl::tungetiff(file.as_raw_fd(), &mut request as *mut _ as *mut c_uint)?;
+    }
+    Ok(request)
+}
+
+/// Get flags (`IFF_*`) of the device attached to the file descriptor (`TUNGETIFF`), with `IFF_NO_PI`
+/// read from sysfs (`/sys/class/net/<name>/tun_flags`), as `TUNGETIFF` reports `IFF_NOFILTER` (set
+/// when no socket filter is attached), which has the same value. If sysfs cannot be read, flags are
+/// returned as reported.
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+pub fn tun_get_flags(file: &File) -> Result<c_int, CreationError> {
+    let request = tun_get_interface(file)?;
+    let flags = request.get_fru().get_flags() as c_int;
+    let tun_flags = read_sysfs(&request.get_name().to_string()?, "tun_flags")
+        .ok()
+        .and_then(|value| c_int::from_str_radix(value.trim_start_matches("0x"), 16).ok());
+    Ok(match tun_flags {
+        Some(tun_flags) => (flags & !IFF_NO_PI) | (tun_flags & IFF_NO_PI),
+        None => flags,
+    })
+}
+
+/// Set if device should be kept after its file descriptor is closed (`TUNSETPERSIST`).
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+/// * `persistent` - Whether device is persistent.
+pub fn tun_set_persist(file: &File, persistent: bool) -> Result<(), CreationError> {
+    unsafe {
+        ioctl::tunsetpersist(file.as_raw_fd(), persistent as u64)?;
+    }
+    Ok(())
+}
+
+/// Returns flags (`IFF_*`) supported by tun/tap driver of the running kernel (`TUNGETFEATURES`).
+///
+/// # Arguments
+///
+/// * `file` - A file descriptor of `/dev/net/tun`, which does not need to be attached to any device.
+pub fn tun_get_features(file: &File) -> Result<c_uint, CreationError> {
+    let mut features: c_uint = 0;
+    unsafe {
+        ioctl::tungetfeatures(file.as_raw_fd(), &mut features)?;
+    }
+    Ok(features)
+}
+
+/// Returns size of virtio header (`TUNGETVNETHDRSZ`) of tun/tap device, used if device has virtio header
+/// enabled (`IFF_VNET_HDR`). Size is 10 bytes, unless it was changed (`TUNSETVNETHDRSZ`), e.g. to 12 bytes
+/// for mergeable receive buffers.
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+pub fn tun_get_vnet_hdr_size(file: &File) -> Result<c_int, CreationError> {
+    let mut size: c_int = 0;
+    unsafe {
+        ioctl::tungetvnethdrsz(file.as_raw_fd(), &mut size)?;
+    }
+    Ok(size)
+}
+
+/// Set offloads (`TUN_F_*`), which userspace is able to handle (`TUNSETOFFLOAD`).
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+/// * `flags` - Offload flags.
+pub fn tun_set_offload(file: &File, flags: c_uint) -> Result<(), CreationError> {
+    unsafe {
+        ioctl::tunsetoffload(file.as_raw_fd(), flags as u64)?;
+    }
+    Ok(())
+}
+
+/// Set send buffer size of the device (`TUNSETSNDBUF`), limiting memory of frames written to the
+/// device, which were not processed by the kernel yet.
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+/// * `size` - Size of the buffer in bytes.
+pub fn tun_set_sndbuf(file: &File, size: c_int) -> Result<(), CreationError> {
+    unsafe {
+        ioctl::tunsetsndbuf(file.as_raw_fd(), &size)?;
+    }
+    Ok(())
+}
+
+/// Request index of the device, which is created by the following `TUNSETIFF` (`TUNSETIFINDEX`).
+///
+/// # Arguments
+///
+/// * `file` - A file descriptor of `/dev/net/tun`, which was not attached to any device yet.
+/// * `index` - Requested index of the device.
+pub fn tun_set_index(file: &File, index: u32) -> Result<(), CreationError> {
+    let index = index as c_uint;
+    unsafe {
+        ioctl::tunsetifindex(file.as_raw_fd(), &index)?;
+    }
+    Ok(())
+}
+
+/// Set hardware (MAC) address of the device, request must be made with [InterfaceFieldReplaceUnit::hw_address].
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `request` - A request containing name of the device and new address.
+pub fn set_hw_address(socket: &File, request: &InterfaceRequest) -> Result<(), CreationError> {
+    unsafe {
+        ioctl::siocsifhwaddr(socket.as_raw_fd(), request)?;
+    }
+    Ok(())
+}
+
+/// Get hardware (MAC) address of the device.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+pub fn get_hw_address(socket: &File, device_name: &str) -> Result<[u8; 6], CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    unsafe {
+        ioctl::siocgifhwaddr(socket.as_raw_fd(), &mut request)?;
+    }
+    Ok(request.get_fru().get_hw_address())
+}
+
+/// Get flags (`IFF_*`) of the device.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+pub fn get_flags(socket: &File, device_name: &str) -> Result<c_short, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    unsafe {
+        ioctl::siocgifflags(socket.as_raw_fd(), &mut request)?;
+    }
+    Ok(request.get_fru().get_flags())
+}
+
+/// Get maximal transmission unit of the device.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+pub fn get_mtu(socket: &File, device_name: &str) -> Result<c_int, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    unsafe {
+        ioctl::siocgifmtu(socket.as_raw_fd(), &mut request)?;
+    }
+    Ok(request.get_fru().get_mtu())
+}
+
+/// Get length of transmit queue of the device (`txqueuelen`).
+pub fn get_tx_queue_len(socket: &File, device_name: &str) -> Result<c_int, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    unsafe {
+        ioctl::siocgiftxqlen(socket.as_raw_fd(), &mut request)?;
+    }
+    Ok(request.get_fru().get_tx_queue_len())
+}
+
+/// Get IPv4 address of the device, or `None` if it does not have any.
+pub fn get_address(socket: &File, device_name: &str) -> Result<Option<Ipv4Addr>, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    match unsafe { ioctl::siocgifaddr(socket.as_raw_fd(), &mut request) } {
+        Err(nix::Error::Sys(Errno::EADDRNOTAVAIL)) => Ok(None),
+        result => {
+            result?;
+            Ok(request.get_fru().get_address())
+        }
+    }
+}
+
+/// Get IPv4 netmask of the device, or `None` if it does not have any address.
+pub fn get_netmask(socket: &File, device_name: &str) -> Result<Option<Ipv4Addr>, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    match unsafe { ioctl::siocgifnetmask(socket.as_raw_fd(), &mut request) } {
+        Err(nix::Error::Sys(Errno::EADDRNOTAVAIL)) => Ok(None),
+        result => {
+            result?;
+            Ok(request.get_fru().get_netmask())
+        }
+    }
+}
+
+/// Get IPv4 broadcast address of the device, or `None` if it does not have any address.
+pub fn get_broadcast_address(socket: &File, device_name: &str) -> Result<Option<Ipv4Addr>, CreationError> {
+    let mut request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::new())?;
+    match unsafe { ioctl::siocgifbrdaddr(socket.as_raw_fd(), &mut request) } {
+        Err(nix::Error::Sys(Errno::EADDRNOTAVAIL)) => Ok(None),
+        result => {
+            result?;
+            Ok(request.get_fru().get_broadcast_address())
+        }
+    }
+}
+
+/// Set IPv4 address of the device.
+pub fn set_address(socket: &File, device_name: &str, address: Ipv4Addr) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::address(address))?;
+    unsafe {
+        ioctl::siocsifaddr(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Set IPv4 netmask of the device.
+pub fn set_netmask(socket: &File, device_name: &str, netmask: Ipv4Addr) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::netmask(netmask))?;
+    unsafe {
+        ioctl::siocsifnetmask(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Set IPv4 broadcast address of the device.
+pub fn set_broadcast_address(socket: &File, device_name: &str, address: Ipv4Addr) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::broadcast_address(address))?;
+    unsafe {
+        ioctl::siocsifbrdaddr(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Ethtool commands (`ETHTOOL_*`) and string set of statistics names.
+const ETHTOOL_GCOALESCE: u32 = 0x0e;
+const ETHTOOL_SCOALESCE: u32 = 0x0f;
+const ETHTOOL_GSTRINGS: u32 = 0x1b;
+const ETHTOOL_GSTATS: u32 = 0x1d;
+const ETHTOOL_GSSET_INFO: u32 = 0x37;
+const ETHTOOL_SRXFHINDIR: u32 = 0x39;
+const ETH_SS_STATS: u32 = 1;
+/// Length of ethtool string, names are nul padded to this length.
+const ETH_GSTRING_LEN: usize = 32;
+
+/// Execute ethtool command (`SIOCETHTOOL`), `command` contains the command structure, which is
+/// updated by the kernel.
+fn ethtool(socket: &File, device_name: &str, command: &mut [u8]) -> Result<(), CreationError> {
+    let data = InterfaceFieldReplaceUnit::data(command.as_mut_ptr() as *mut c_void);
+    let request = InterfaceRequest::new(device_name, data)?;
+    unsafe {
+        ioctl::siocethtool(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Set RX flow hash indirection table of the device (`SIOCETHTOOL` with `ETHTOOL_SRXFHINDIR`).
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+/// * `indir` - Queue index for each entry of the table, empty table resets it to default.
+pub fn set_rxfh_indir(socket: &File, device_name: &str, indir: &[u32]) -> Result<(), CreationError> {
+    // struct ethtool_rxfh_indir: cmd, size, ring_index[size]
+    let mut command = Vec::with_capacity(8 + 4 * indir.len());
+    for value in [ETHTOOL_SRXFHINDIR, indir.len() as u32].iter().chain(indir) {
+        command.extend_from_slice(&value.to_ne_bytes());
+    }
+    ethtool(socket, device_name, &mut command)
+}
+
+/// Number of fields of `struct ethtool_coalesce` following the command.
+pub const COALESCE_FIELDS: usize = 22;
+
+/// Get interrupt coalescing parameters of the device (`ETHTOOL_GCOALESCE`).
+///
+/// # Returns
+///
+/// Fields of `struct ethtool_coalesce` (`rx_coalesce_usecs`, `rx_max_coalesced_frames`, ...) in their order.
+pub fn get_coalesce(socket: &File, device_name: &str) -> Result<[u32; COALESCE_FIELDS], CreationError> {
+    let mut command = vec![0u8; 4 * (1 + COALESCE_FIELDS)];
+    command[0..4].copy_from_slice(&ETHTOOL_GCOALESCE.to_ne_bytes());
+    ethtool(socket, device_name, &mut command)?;
+    let mut fields = [0u32; COALESCE_FIELDS];
+    for (field, value) in fields.iter_mut().zip(command[4..].chunks_exact(4)) {
+        *field = u32::from_ne_bytes(value.try_into().unwrap());
+    }
+    Ok(fields)
+}
+
+/// Set interrupt coalescing parameters of the device (`ETHTOOL_SCOALESCE`), see [get_coalesce].
+pub fn set_coalesce(socket: &File, device_name: &str, fields: &[u32; COALESCE_FIELDS]) -> Result<(), CreationError> {
+    let mut command = ETHTOOL_SCOALESCE.to_ne_bytes().to_vec();
+    for field in fields {
+        command.extend_from_slice(&field.to_ne_bytes());
+    }
+    ethtool(socket, device_name, &mut command)
+}
+
+/// Returns names and values of driver specific statistics of the device (`ETHTOOL_GSTATS`), like `ethtool -S`.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+pub fn ethtool_stats(socket: &File, device_name: &str) -> Result<Vec<(String, u64)>, CreationError> {
+    let read_u32 = |data: &[u8], offset: usize| u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    // struct ethtool_sset_info: cmd, reserved, sset_mask, data[]
+    let mut info = vec![0u8; 20];
+    info[0..4].copy_from_slice(&ETHTOOL_GSSET_INFO.to_ne_bytes());
+    info[8..16].copy_from_slice(&(1u64 << ETH_SS_STATS).to_ne_bytes());
+    ethtool(socket, device_name, &mut info)?;
+    // Set of driver without statistics is cleared from the mask
+    if u64::from_ne_bytes(info[8..16].try_into().unwrap()) == 0 {
+        return Ok(Vec::new());
+    }
+    let count = read_u32(&info, 16) as usize;
+
+    // struct ethtool_gstrings: cmd, string_set, len, data[len * ETH_GSTRING_LEN]
+    let mut strings = vec![0u8; 12 + count * ETH_GSTRING_LEN];
+    strings[0..4].copy_from_slice(&ETHTOOL_GSTRINGS.to_ne_bytes());
+    strings[4..8].copy_from_slice(&ETH_SS_STATS.to_ne_bytes());
+    strings[8..12].copy_from_slice(&(count as u32).to_ne_bytes());
+    ethtool(socket, device_name, &mut strings)?;
+
+    // struct ethtool_stats: cmd, n_stats, data[n_stats]
+    let mut stats = vec![0u8; 8 + count * 8];
+    stats[0..4].copy_from_slice(&ETHTOOL_GSTATS.to_ne_bytes());
+    stats[4..8].copy_from_slice(&(count as u32).to_ne_bytes());
+    ethtool(socket, device_name, &mut stats)?;
+
+    let count = count.min(read_u32(&strings, 8) as usize).min(read_u32(&stats, 4) as usize);
+    Ok((0..count)
+        .map(|i| {
+            let name = &strings[12 + i * ETH_GSTRING_LEN..12 + (i + 1) * ETH_GSTRING_LEN];
+            let name = name.split(|byte| *byte == 0).next().unwrap_or(&[]);
+            let value = u64::from_ne_bytes(stats[8 + i * 8..16 + i * 8].try_into().unwrap());
+            (String::from_utf8_lossy(name).into_owned(), value)
+        })
+        .collect())
+}
+
+/// Set maximal transmission unit of the device.
+pub fn set_mtu(socket: &File, device_name: &str, mtu: c_int) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::mtu(mtu))?;
+    unsafe {
+        ioctl::siocsifmtu(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Set length of transmit queue of the device (`txqueuelen`).
+pub fn set_tx_queue_len(socket: &File, device_name: &str, tx_queue_len: c_int) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::tx_queue_len(tx_queue_len))?;
+    unsafe {
+        ioctl::siocsiftxqlen(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Set flags (`IFF_*`) of the device.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+/// * `flags` - New flags of the device.
+pub fn set_flags(socket: &File, device_name: &str, flags: c_short) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::flags(flags))?;
+    unsafe {
+        ioctl::siocsifflags(socket.as_raw_fd(), &request)?;
+    }
+    Ok(())
+}
+
+/// Join (`SIOCADDMULTI`) or leave (`SIOCDELMULTI`) link layer multicast group.
+///
+/// # Arguments
+///
+/// * `socket` - A control socket, see [get_control_socket].
+/// * `device_name` - Name of the device.
+/// * `group` - Multicast MAC address.
+/// * `join` - Whether group is joined or left.
+pub fn set_multicast(socket: &File, device_name: &str, group: [u8; 6], join: bool) -> Result<(), CreationError> {
+    let request = InterfaceRequest::new(device_name, InterfaceFieldReplaceUnit::multicast_address(group))?;
+    unsafe {
+        if join {
+            ioctl::siocaddmulti(socket.as_raw_fd(), &request)?;
+        } else {
+            ioctl::siocdelmulti(socket.as_raw_fd(), &request)?;
+        }
+    }
+    Ok(())
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+/// Filter of frames, which are passed from tap device to the userspace (`struct tun_filter`),
+/// containing at most one address.
+pub struct TxFilter {
+    flags: c_ushort,
+    count: c_ushort,
+    address: [u8; 6],
+}
+
+impl TxFilter {
+    /// Filter passing every frame.
+    pub fn disabled() -> Self {
+        Self {
+            flags: 0,
+            count: 0,
+            address: [0; 6],
+        }
+    }
+
+    /// Filter passing only frames with given destination address.
+    pub fn address(address: [u8; 6]) -> Self {
+        Self {
+            flags: 0,
+            count: 1,
+            address,
+        }
+    }
+}
+
+/// Set filter of frames passed from tap device to the userspace (`TUNSETTXFILTER`).
+///
+/// # Arguments
+///
+/// * `file` - An opened tap device.
+/// * `filter` - Filter to be set.
+pub fn tun_set_tx_filter(file: &File, filter: &TxFilter) -> Result<(), CreationError> {
+    unsafe {
+        ioctl::tunsettxfilter(file.as_raw_fd(), filter as *const _ as *const c_uint)?;
+    }
+    Ok(())
+}
+
+/// Attach classic BPF socket filter to tap device (`TUNATTACHFILTER`), frames rejected by the filter
+/// are dropped instead of being passed to the userspace.
+///
+/// # Arguments
+///
+/// * `file` - An opened tap device.
+/// * `program` - Instructions of the filter.
+pub fn tun_attach_filter(file: &File, program: &[sock_filter]) -> Result<(), CreationError> {
+    let program = sock_fprog {
+        len: program.len() as c_ushort,
+        filter: program.as_ptr() as *mut sock_filter,
+    };
+    unsafe {
+        ioctl::tunattachfilter(file.as_raw_fd(), &program)?;
+    }
+    Ok(())
+}
+
+/// Detach socket filter attached by [tun_attach_filter] from tap device (`TUNDETACHFILTER`).
+///
+/// # Arguments
+///
+/// * `file` - An opened tap device.
+pub fn tun_detach_filter(file: &File) -> Result<(), CreationError> {
+    let program = sock_fprog { len: 0, filter: std::ptr::null_mut() };
+    unsafe {
+        ioctl::tundetachfilter(file.as_raw_fd(), &program)?;
+    }
+    Ok(())
+}
+
+/// Attach eBPF socket filter to tun/tap device (`TUNSETFILTEREBPF`), or detach it, if `prog_fd` is -1.
+///
+/// # Arguments
+///
+/// * `file` - An opened tun/tap device.
+/// * `prog_fd` - File descriptor of loaded `BPF_PROG_TYPE_SOCKET_FILTER` program, or -1.
+pub fn tun_set_filter_ebpf(file: &File, prog_fd: RawFd) -> Result<(), CreationError> {
+    let mut prog_fd: c_int = prog_fd;
+    unsafe {
+        ioctl::tunsetfilterebpf(file.as_raw_fd(), &mut prog_fd)?;
     }
     Ok(())
 }
 
 /// IOCTL calls (which are more or less a black magic) are unsafe and hard to use, that's why
 /// they are in such restrictive module, which allows calling them only from wrappers defined util.rs.
-pub(self) mod ioctl {
-    use nix::ioctl_write_int;
+mod ioctl {
+    use libc::{c_int, c_uint};
+    use nix::{ioctl_read, ioctl_read_bad, ioctl_write_int, ioctl_write_ptr, ioctl_write_ptr_bad};
+    use super::InterfaceRequest;
     // ioctl(fd, TUNSETIFF, ifreq) -> Used to setup the tun/tap device on
     // opened file descriptor of /dev/net/tun
     ioctl_write_int!(tunsetiff, b'T', 202);
@@ -219,4 +1184,178 @@ pub(self) mod ioctl {
     ioctl_write_int!(tunsetowner, b'T', 204);
     // ioctl(fd, TUNSETGROUP, gid) -> Set owning group of opened tun/tap device to group with given GID.
     ioctl_write_int!(tunsetgroup, b'T', 206);
+    // ioctl(fd, TUNGETFEATURES, flags) -> Get flags supported by the driver
+    ioctl_read!(tungetfeatures, b'T', 207, c_uint);
+    // ioctl(fd, TUNGETIFF, ifreq) -> Get name and flags of the device attached to the file descriptor
+    ioctl_read!(tungetiff, b'T', 210, c_uint);
+    // ioctl(fd, TUNSETOFFLOAD, flags) -> Set offloads, which userspace is able to handle
+    ioctl_write_int!(tunsetoffload, b'T', 208);
+    // ioctl(fd, TUNSETTXFILTER, tun_filter) -> Set filter of frames passed to the userspace (tap only)
+    ioctl_write_ptr!(tunsettxfilter, b'T', 209, c_uint);
+    // ioctl(fd, TUNSETSNDBUF, int) -> Set send buffer size of the device
+    ioctl_write_ptr!(tunsetsndbuf, b'T', 212, c_int);
+    // ioctl(fd, TUNATTACHFILTER, sock_fprog) -> Attach socket filter to the device (tap only)
+    ioctl_write_ptr!(tunattachfilter, b'T', 213, libc::sock_fprog);
+    // ioctl(fd, TUNDETACHFILTER, sock_fprog) -> Detach socket filter from the device (tap only)
+    ioctl_write_ptr!(tundetachfilter, b'T', 214, libc::sock_fprog);
+    // ioctl(fd, TUNGETVNETHDRSZ, int) -> Get size of virtio header of the device
+    ioctl_read!(tungetvnethdrsz, b'T', 215, c_int);
+    // ioctl(fd, TUNSETIFINDEX, index) -> Request index of the device created by following TUNSETIFF
+    ioctl_write_ptr!(tunsetifindex, b'T', 218, c_uint);
+    // ioctl(fd, TUNSETFILTEREBPF, int) -> Attach eBPF socket filter to the device, -1 detaches it
+    ioctl_read!(tunsetfilterebpf, b'T', 225, c_int);
+    // ioctl(socket, SIOCSIFHWADDR, ifreq) -> Set hardware address of the device
+    ioctl_write_ptr_bad!(siocsifhwaddr, libc::SIOCSIFHWADDR, InterfaceRequest);
+    // ioctl(socket, SIOCGIFHWADDR, ifreq) -> Get hardware address of the device
+    ioctl_read_bad!(siocgifhwaddr, libc::SIOCGIFHWADDR, InterfaceRequest);
+    // ioctl(socket, SIOCGIFFLAGS, ifreq) -> Get flags of the device
+    ioctl_read_bad!(siocgifflags, libc::SIOCGIFFLAGS, InterfaceRequest);
+    // ioctl(socket, SIOCGIFMTU, ifreq) -> Get maximal transmission unit of the device
+    ioctl_read_bad!(siocgifmtu, libc::SIOCGIFMTU, InterfaceRequest);
+    // ioctl(socket, SIOCSIFMTU, ifreq) -> Set maximal transmission unit of the device
+    ioctl_write_ptr_bad!(siocsifmtu, libc::SIOCSIFMTU, InterfaceRequest);
+    // ioctl(socket, SIOCGIFTXQLEN, ifreq) -> Get length of transmit queue of the device
+    ioctl_read_bad!(siocgiftxqlen, libc::SIOCGIFTXQLEN, InterfaceRequest);
+    // ioctl(socket, SIOCSIFTXQLEN, ifreq) -> Set length of transmit queue of the device
+    ioctl_write_ptr_bad!(siocsiftxqlen, libc::SIOCSIFTXQLEN, InterfaceRequest);
+    // ioctl(socket, SIOCGIFADDR, ifreq) -> Get IPv4 address of the device
+    ioctl_read_bad!(siocgifaddr, libc::SIOCGIFADDR, InterfaceRequest);
+    // ioctl(socket, SIOCGIFNETMASK, ifreq) -> Get IPv4 netmask of the device
+    ioctl_read_bad!(siocgifnetmask, libc::SIOCGIFNETMASK, InterfaceRequest);
+    // ioctl(socket, SIOCGIFBRDADDR, ifreq) -> Get IPv4 broadcast address of the device
+    ioctl_read_bad!(siocgifbrdaddr, libc::SIOCGIFBRDADDR, InterfaceRequest);
+    // ioctl(socket, SIOCSIFADDR, ifreq) -> Set IPv4 address of the device
+    ioctl_write_ptr_bad!(siocsifaddr, libc::SIOCSIFADDR, InterfaceRequest);
+    // ioctl(socket, SIOCSIFNETMASK, ifreq) -> Set IPv4 netmask of the device
+    ioctl_write_ptr_bad!(siocsifnetmask, libc::SIOCSIFNETMASK, InterfaceRequest);
+    // ioctl(socket, SIOCSIFBRDADDR, ifreq) -> Set IPv4 broadcast address of the device
+    ioctl_write_ptr_bad!(siocsifbrdaddr, libc::SIOCSIFBRDADDR, InterfaceRequest);
+    // ioctl(socket, SIOCSIFFLAGS, ifreq) -> Set flags of the device
+    ioctl_write_ptr_bad!(siocsifflags, libc::SIOCSIFFLAGS, InterfaceRequest);
+    // ioctl(socket, SIOCETHTOOL, ifreq) -> Execute ethtool command pointed to by the request
+    ioctl_write_ptr_bad!(siocethtool, libc::SIOCETHTOOL, InterfaceRequest);
+    // ioctl(socket, SIOCADDMULTI, ifreq) -> Join link layer multicast group
+    ioctl_write_ptr_bad!(siocaddmulti, libc::SIOCADDMULTI, InterfaceRequest);
+    // ioctl(socket, SIOCDELMULTI, ifreq) -> Leave link layer multicast group
+    ioctl_write_ptr_bad!(siocdelmulti, libc::SIOCDELMULTI, InterfaceRequest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_name_unterminated() {
+        let name = InterfaceName { name: [b'x' as c_char; IFNAMSIZ] };
+        assert_eq!(name.len(), IFNAMSIZ - 1);
+        assert!(InterfaceName::empty().is_empty());
+    }
+
+    #[test]
+    fn loopback_index_and_name() {
+        let index = interface_index("lo").unwrap();
+        assert_eq!(interface_name(index).as_deref(), Some("lo"));
+        assert_eq!(interface_name(u32::MAX), None);
+        assert!(matches!(interface_index("l\0o"), Err(CreationError::InvalidName(StringError::UnexpectedNull(1)))));
+    }
+
+    #[test]
+    fn missing_interface_is_gone() {
+        assert!(matches!(interface_index("tippytap-none"), Err(CreationError::DeviceGone)));
+    }
+
+    #[test]
+    fn sockaddr_round_trip() {
+        let address = Ipv4Addr::new(192, 168, 10, 1);
+        assert_eq!(sockaddr_to_inet(&inet_to_sockaddr(address)), Some(address));
+        let mut other: sockaddr = unsafe { std::mem::zeroed() };
+        other.sa_family = AF_INET6 as sa_family_t;
+        assert_eq!(sockaddr_to_inet(&other), None);
+    }
+
+    #[test]
+    fn fru_accessors() {
+        assert_eq!(InterfaceFieldReplaceUnit::mtu(9000).get_mtu(), 9000);
+        assert_eq!(InterfaceFieldReplaceUnit::if_index(7).get_if_index(), 7);
+        assert_eq!(InterfaceFieldReplaceUnit::metric(3).get_metric(), 3);
+        let flags = (IFF_UP | IFF_NOARP) as c_short;
+        assert_eq!(InterfaceFieldReplaceUnit::flags(flags).get_flags(), flags);
+        let address = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(InterfaceFieldReplaceUnit::address(address).get_address(), Some(address));
+        let netmask = Ipv4Addr::new(255, 255, 255, 0);
+        assert_eq!(InterfaceFieldReplaceUnit::netmask(netmask).get_netmask(), Some(netmask));
+        let broadcast = Ipv4Addr::new(10, 0, 0, 255);
+        assert_eq!(InterfaceFieldReplaceUnit::broadcast_address(broadcast).get_broadcast_address(), Some(broadcast));
+        let peer = Ipv4Addr::new(10, 0, 0, 2);
+        assert_eq!(InterfaceFieldReplaceUnit::destination_address(peer).get_destination_address(), Some(peer));
+        assert_eq!(InterfaceFieldReplaceUnit::new().get_address(), None);
+    }
+
+    #[test]
+    fn fru_hw_address() {
+        let mac = [0x02, 0x00, 0x5e, 0x10, 0xab, 0xff];
+        let fru = InterfaceFieldReplaceUnit::hw_address(mac);
+        assert_eq!(fru.get_hw_address(), mac);
+        assert_eq!(unsafe { fru.hw_address.sa_family }, ARPHRD_ETHER);
+    }
+
+    #[test]
+    fn fru_multicast_address() {
+        let multicast = InterfaceFieldReplaceUnit::multicast_address([0x01, 0, 0x5e, 0, 0, 1]);
+        assert_eq!(unsafe { multicast.hw_address.sa_family }, AF_UNSPEC as sa_family_t);
+        assert_eq!(multicast.get_hw_address(), [0x01, 0, 0x5e, 0, 0, 1]);
+    }
+
+    #[test]
+    fn fru_tx_queue_len() {
+        assert_eq!(InterfaceFieldReplaceUnit::tx_queue_len(500).get_tx_queue_len(), 500);
+    }
+
+    #[test]
+    fn interface_name_from_str() {
+        let name = InterfaceName::from_str("tap0").unwrap();
+        assert_eq!(name.len(), 4);
+        assert_eq!(name.to_string().unwrap(), "tap0");
+        assert!(InterfaceName::from_str("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn interface_name_too_long() {
+        assert!(InterfaceName::from_str("a".repeat(IFNAMSIZ - 1)).is_ok());
+        match InterfaceName::from_str("a".repeat(20)) {
+            Err(StringError::StringTooLong { max, len }) => {
+                assert_eq!(max, IFNAMSIZ - 1);
+                assert_eq!(len, 20);
+            }
+            other => panic!("unexpected result {:?}", other.map(|name| name.len())),
+        }
+        let error = InterfaceName::from_str("a".repeat(IFNAMSIZ)).unwrap_err();
+        assert_eq!(error.to_string(), "c_string too long, has 16 characters, but can be at most 15 characters");
+    }
+
+    #[test]
+    fn interface_name_invalid_character() {
+        assert!(matches!(InterfaceName::from_str("tap\u{e9}"), Err(StringError::InvalidCharacter(3))));
+        assert!(matches!(InterfaceName::from_str("ta\0p"), Err(StringError::InvalidCharacter(2))));
+    }
+
+    #[test]
+    fn request_rejects_long_name() {
+        let fru = InterfaceFieldReplaceUnit::new();
+        assert!(matches!(InterfaceRequest::new("x".repeat(20), fru), Err(StringError::StringTooLong { len: 20, .. })));
+        let request = InterfaceRequest::tun_set_request("tun7", IFF_TUN as c_short).unwrap();
+        assert_eq!(request.get_name().to_string().unwrap(), "tun7");
+        assert_eq!(request.get_fru().get_flags(), IFF_TUN as c_short);
+    }
+
+    #[test]
+    fn interface_name_from_os_str() {
+        let name = InterfaceName::from_os_str(OsStr::new("veth1")).unwrap();
+        assert_eq!(name.to_string().unwrap(), "veth1");
+        let invalid = OsStr::from_bytes(b"eth\xff0");
+        assert!(matches!(InterfaceName::from_os_str(invalid), Err(StringError::InvalidCharacter(3))));
+        let long = "b".repeat(IFNAMSIZ + 2);
+        assert!(matches!(InterfaceName::from_os_str(OsStr::new(&long)),
+                         Err(StringError::StringTooLong { len, .. }) if len == IFNAMSIZ + 2));
+    }
 }